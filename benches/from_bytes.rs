@@ -0,0 +1,95 @@
+//! Compare the `StdContentEncoding::from_bytes` matcher against the chain of
+//! `eq_ignore_ascii_case` comparisons it replaced, over a realistic mix of tokens.
+//!
+//! Run with `cargo bench --bench from_bytes`.
+
+#[macro_use]
+extern crate criterion;
+extern crate uhttp_content_encoding;
+
+use criterion::Criterion;
+use std::hint::black_box;
+use uhttp_content_encoding::StdContentEncoding;
+
+/// Tokens weighted roughly by how often they appear in real traffic, with some casing
+/// variation, aliases, and unknown names mixed in.
+const TOKENS: &[&[u8]] = &[
+    b"gzip", b"br", b"gzip", b"deflate", b"zstd", b"GZIP", b"br", b"identity", b"gzip",
+    b"x-gzip", b"Br", b"custom-enc", b"gzip", b"", b"zstd", b"compress", b"aes128gcm",
+    b"gzip", b"Deflate", b"x-some-long-custom-encoding",
+];
+
+/// Previous implementation of `from_bytes`, kept as a baseline.
+///
+/// This isn't inlined, so that like the library function, it isn't specialized for the
+/// benchmark loop.
+#[inline(never)]
+fn from_bytes_chain(s: &[u8]) -> Option<StdContentEncoding> {
+    use StdContentEncoding::*;
+
+    if s.eq_ignore_ascii_case(b"aes128gcm") {
+        Some(Aes128Gcm)
+    } else if s.eq_ignore_ascii_case(b"br") {
+        Some(Brotli)
+    } else if s.eq_ignore_ascii_case(b"compress") || s.eq_ignore_ascii_case(b"x-compress") {
+        Some(Compress)
+    } else if s.eq_ignore_ascii_case(b"dcb") {
+        Some(Dcb)
+    } else if s.eq_ignore_ascii_case(b"dcz") {
+        Some(Dcz)
+    } else if s.eq_ignore_ascii_case(b"deflate") {
+        Some(Deflate)
+    } else if s.eq_ignore_ascii_case(b"exi") {
+        Some(EfficientXML)
+    } else if s.eq_ignore_ascii_case(b"gzip") || s.eq_ignore_ascii_case(b"x-gzip") {
+        Some(Gzip)
+    } else if s.eq_ignore_ascii_case(b"identity") {
+        Some(Identity)
+    } else if s.eq_ignore_ascii_case(b"pack200-gzip") {
+        Some(Pack200Gzip)
+    } else if s.eq_ignore_ascii_case(b"zstd") {
+        Some(Zstd)
+    } else if s.is_empty() {
+        Some(Identity)
+    } else {
+        None
+    }
+}
+
+fn bench_from_bytes(c: &mut Criterion) {
+    for &token in TOKENS {
+        assert_eq!(StdContentEncoding::from_bytes(token), from_bytes_chain(token));
+    }
+
+    let mut group = c.benchmark_group("from_bytes");
+
+    group.bench_function("chain/mix", |b| b.iter(|| {
+        for &token in TOKENS {
+            black_box(from_bytes_chain(black_box(token)));
+        }
+    }));
+
+    group.bench_function("match/mix", |b| b.iter(|| {
+        for &token in TOKENS {
+            black_box(StdContentEncoding::from_bytes(black_box(token)));
+        }
+    }));
+
+    // A single repeated token, as when most traffic uses the same encoding.
+    for &token in &[&b"gzip"[..], b"br", b"x-custom"] {
+        let name = std::str::from_utf8(token).unwrap();
+
+        group.bench_function(format!("chain/{}", name), |b| b.iter(|| {
+            black_box(from_bytes_chain(black_box(token)))
+        }));
+
+        group.bench_function(format!("match/{}", name), |b| b.iter(|| {
+            black_box(StdContentEncoding::from_bytes(black_box(token)))
+        }));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_from_bytes);
+criterion_main!(benches);
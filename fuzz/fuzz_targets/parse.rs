@@ -0,0 +1,14 @@
+//! Fuzz every parser over arbitrary header bytes.
+//!
+//! Run from the repository root with `cargo +nightly fuzz run parse -- -timeout=5`, so
+//! inputs that take superlinear time are reported as timeouts.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate uhttp_content_encoding;
+
+fuzz_target!(|data: &[u8]| {
+    uhttp_content_encoding::fuzz_parse(data);
+});
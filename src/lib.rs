@@ -16,6 +16,75 @@
 //! assert_eq!(encs.next(), Some(ContentEncoding::Std(StdContentEncoding::Gzip)));
 //! assert_eq!(encs.next(), None);
 //! ```
+//!
+//! ## Features
+//!
+//! The parser itself only depends on `core`, so the crate can be used in `no_std`
+//! environments by disabling default features.
+//!
+//! - `std` (default): implements `std::error::Error` for error types. Implies `alloc`.
+//! - `alloc`: enables functions that allocate, such as
+//!   [`encode_content_encodings`](fn.encode_content_encodings.html).
+//! - `http`: adds [`content_encodings_header`](fn.content_encodings_header.html) for
+//!   parsing `http::HeaderValue`s.
+//! - `serde`: implements `Serialize` and `Deserialize` for encodings, using their token
+//!   strings.
+//! - `smallvec`: adds [`parse_all_small`](fn.parse_all_small.html) for collecting layers
+//!   without allocating.
+//! - `ffi`: adds the [`ffi`](ffi/index.html) module of C-compatible functions. Implies
+//!   `std`.
+//! - `tracing`: emits a debug event for each header parsed by
+//!   [`content_encodings`](fn.content_encodings.html), or by a function built on it, with
+//!   its layer count and any nonstandard or invalid tokens. Each header passed to a public
+//!   function is traced once, however many times it's scanned.
+//!
+//! ## Upgrading
+//!
+//! Version 0.6 is a breaking release. The misspelled `StdContentEncoding::Brottli` variant
+//! has been renamed to [`Brotli`](enum.StdContentEncoding.html#variant.Brotli).
+//! `StdContentEncoding::Brottli` remains as a deprecated associated constant, but can't be
+//! brought into scope by a glob import of the variants, so any unqualified use of `Brottli`
+//! must be renamed. This matters most in `match` arms, where an unqualified `Brottli` would
+//! silently match every value.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "http")]
+extern crate http;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "smallvec")]
+extern crate smallvec;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Create an array of [`ContentEncoding`](enum.ContentEncoding.html)s from a list of
+/// [`StdContentEncoding`](enum.StdContentEncoding.html) variant names and `Other("name")`
+/// entries, such as `content_encodings![Gzip, Brotli, Other("custom")]`.
+///
+/// Misspelled variant names fail to compile, and the expansion is a constant expression,
+/// so it can be used to initialize `const`s and `static`s.
+#[macro_export]
+macro_rules! content_encodings {
+    (@one Other($name:expr)) => {
+        $crate::ContentEncoding::Other($name)
+    };
+    (@one $enc:ident) => {
+        $crate::ContentEncoding::Std($crate::StdContentEncoding::$enc)
+    };
+    ($($enc:ident $(($name:expr))*),* $(,)*) => {
+        [$($crate::content_encodings!(@one $enc $(($name))*)),*]
+    };
+}
 
 /// Create an iterator over content encoding layers from the given string in [the
 /// form](https://tools.ietf.org/html/rfc7231#section-3.1.2.2) used by the
@@ -23,152 +92,5053 @@
 ///
 /// Encodings are yielded in the order they must be decoded, with the outermost layer
 /// yielded first and the innermost layer yielded last.
-pub fn content_encodings<'a>(s: &'a str) -> impl Iterator<Item = ContentEncoding<'a>> {
-    s.split(',').rev().map(ContentEncoding::new)
+///
+/// With the `tracing` feature, a debug event summarizing the header is emitted when a
+/// subscriber is interested in it.
+pub fn content_encodings<'a>(s: &'a str) -> ContentEncodings<'a> {
+    #[cfg(feature = "tracing")]
+    trace_header(s);
+
+    parse_layers(s)
 }
 
-/// HTTP content encoding scheme.
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
-pub enum ContentEncoding<'a> {
-    /// Standard defined scheme.
-    Std(StdContentEncoding),
-    /// Unknown/nonstandard scheme with the contained name.
-    ///
-    /// This is guaranteed to have no surrounding whitespace and requires case-insensitive
-    /// comparison to other strings.
-    Other(&'a str),
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), without emitting a tracing event.
+///
+/// Functions that parse a header more than once use this after their first pass, so each
+/// header given to a public function is traced once.
+fn parse_layers<'a>(s: &'a str) -> ContentEncodings<'a> {
+    ContentEncodings {
+        raw: content_encodings_bytes(s.as_bytes()),
+        header: s,
+        remaining: count_commas(s.as_bytes()) + 1,
+    }
 }
 
-impl<'a> ContentEncoding<'a> {
-    /// Parse a new `ContentEncoding` from the given string.
-    pub fn new(s: &'a str) -> Self {
-        let s = s.trim();
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), recognizing the given
+/// application-specific codings.
+///
+/// Codings that aren't IANA-registered, such as `bzip2`, are sometimes used between
+/// internal services. Each `(name, id)` in `extra` makes a coding with that name, compared
+/// ignoring ASCII case, yield as `Extension(id)` rather than `Other`, so several aliases,
+/// such as `bzip2` and `x-bzip2`, can share an identifier. Standard encodings take
+/// precedence over extensions of the same name.
+pub fn parse_with_extensions<'a, 'b>(s: &'a str, extra: &'b [(&'b str, u16)])
+    -> impl Iterator<Item = ExtendedEncoding<'a>> + 'b
+    where 'a: 'b
+{
+    content_encodings(s).map(move |enc| match enc {
+        ContentEncoding::Std(enc) => ExtendedEncoding::Std(enc),
+        ContentEncoding::Other(name) => {
+            match extra.iter().find(|&&(ext, _)| ext.eq_ignore_ascii_case(name)) {
+                Some(&(_, id)) => ExtendedEncoding::Extension(id),
+                None => ExtendedEncoding::Other(name),
+            }
+        },
+    })
+}
+
+/// Create an iterator over content encoding layers from any borrowed string type, like
+/// [`content_encodings`](fn.content_encodings.html).
+///
+/// This is a single entry point for header values that are sometimes owned and sometimes
+/// borrowed, such as `String`, `&str`, and `Cow<str>`. Nonstandard names borrow from the
+/// value, so it must outlive the iterator.
+pub fn content_encodings_from<'a, S: AsRef<str> + ?Sized>(s: &'a S) -> ContentEncodings<'a> {
+    content_encodings(s.as_ref())
+}
+
+/// Emit a debug event with the layer count and the nonstandard and invalid tokens of the
+/// given header.
+///
+/// The header is only scanned when the event is enabled, and tokens are formatted lazily
+/// by the subscriber, so nothing is allocated here.
+#[cfg(feature = "tracing")]
+fn trace_header(s: &str) {
+    if !tracing::enabled!(tracing::Level::DEBUG) {
+        return;
+    }
+
+    tracing::debug!(
+        layers = count_commas(s.as_bytes()) + 1,
+        unknown = %TraceTokens { header: s, invalid: false },
+        invalid = %TraceTokens { header: s, invalid: true },
+        "parsed Content-Encoding header"
+    );
+}
+
+/// Writes the comma-separated nonstandard or invalid tokens of a header for tracing.
+#[cfg(feature = "tracing")]
+struct TraceTokens<'a> {
+    header: &'a str,
+    invalid: bool,
+}
+
+#[cfg(feature = "tracing")]
+impl<'a> core::fmt::Display for TraceTokens<'a> {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let tokens = self.header.split(',').filter_map(|s| {
+            match (ContentEncoding::new_strict(s), self.invalid) {
+                (Ok(ContentEncoding::Other(t)), false) => Some(t),
+                (Err(e), true) => Some(e.token()),
+                _ => None,
+            }
+        });
+
+        for (i, token) in tokens.enumerate() {
+            if i > 0 {
+                fmt.write_str(", ")?;
+            }
 
-        match s.parse() {
-            Ok(enc) => ContentEncoding::Std(enc),
-            Err(_) => ContentEncoding::Other(s),
+            fmt.write_str(token)?;
         }
+
+        Ok(())
     }
 }
 
-/// Standard content encoding scheme, as defined by
-/// [IANA](http://www.iana.org/assignments/http-parameters/http-parameters.xhtml#content-coding).
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
-pub enum StdContentEncoding {
-    /// Brottli compressed data format.
-    Brottli,
-    /// Unix "compress" data format.
-    Compress,
-    /// Deflate compressed data format.
-    Deflate,
-    /// W3C Efficient XML Interchange.
-    EfficientXML,
-    /// Gzip compressed data format.
-    Gzip,
-    /// No encoding.
-    Identity,
-    /// Java archive network transfer format.
-    Pack200Gzip,
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), validating each layer.
+///
+/// Each element is parsed with
+/// [`ContentEncoding::new_strict`](enum.ContentEncoding.html#method.new_strict), so
+/// malformed elements yield an error in place while the remaining layers are still
+/// yielded. Errors borrow the malformed element, so this never allocates.
+pub fn content_encodings_checked<'a>(s: &'a str)
+    -> impl Iterator<Item = Result<ContentEncoding<'a>, InvalidTokenError<'a>>>
+{
+    s.split(',').rev().map(ContentEncoding::new_strict)
 }
 
-impl std::str::FromStr for StdContentEncoding {
-    type Err = ();
-
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        use self::StdContentEncoding::*;
-
-        // Values are case-insensitive [RFC7231§3.1.2.1].
-        if s.eq_ignore_ascii_case("br") {
-            Ok(Brottli)
-        } else if s.eq_ignore_ascii_case("compress") {
-            Ok(Compress)
-        } else if s.eq_ignore_ascii_case("deflate") {
-            Ok(Deflate)
-        } else if s.eq_ignore_ascii_case("exi") {
-            Ok(EfficientXML)
-        } else if s.eq_ignore_ascii_case("gzip") {
-            Ok(Gzip)
-        } else if s.eq_ignore_ascii_case("identity") {
-            Ok(Identity)
-        } else if s.eq_ignore_ascii_case("pack200-gzip") {
-            Ok(Pack200Gzip)
-        } else if s.is_empty() {
-            // Assume empty means identity [RFC7231§5.3.4].
-            Ok(Identity)
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings_checked`](fn.content_encodings_checked.html), with the given maximum
+/// token length in bytes instead of
+/// [`DEFAULT_MAX_TOKEN_LEN`](constant.DEFAULT_MAX_TOKEN_LEN.html).
+pub fn content_encodings_checked_max<'a>(s: &'a str, max_len: usize)
+    -> impl Iterator<Item = Result<ContentEncoding<'a>, InvalidTokenError<'a>>>
+{
+    s.split(',').rev().map(move |e| ContentEncoding::new_strict_max(e, max_len))
+}
+
+/// Maximum length in bytes of a token accepted by the strict parsers, such as
+/// [`ContentEncoding::new_strict`](enum.ContentEncoding.html#method.new_strict).
+///
+/// Standard tokens are at most 12 bytes, so this only rejects absurd nonstandard names.
+/// It's separate from the layer limit of
+/// [`content_encodings_limited`](fn.content_encodings_limited.html).
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 256;
+
+/// Create an iterator over the elements of the given `Content-Encoding` string, classifying
+/// each as a standard encoding, a well-formed unknown encoding, or a malformed element.
+///
+/// This distinguishes nonstandard encodings that can be passed through from elements that
+/// violate the token grammar and should be rejected. Elements are yielded outermost first,
+/// like [`content_encodings`](fn.content_encodings.html), and empty elements are
+/// `Known(Identity)`.
+pub fn content_encoding_segments<'a>(s: &'a str) -> impl Iterator<Item = Segment<'a>> {
+    s.split(',').rev().map(Segment::new)
+}
+
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), paired with each layer's token as
+/// written.
+///
+/// Tokens keep the peer's original casing and spelling, even for standard encodings, so
+/// `GZIP` yields `(Std(Gzip), "GZIP")` and `x-gzip` yields `(Std(Gzip), "x-gzip")`, which
+/// suits logging raw traffic. Surrounding whitespace is removed, and an empty element
+/// yields an empty token.
+pub fn content_encodings_verbatim<'a>(s: &'a str)
+    -> impl Iterator<Item = (ContentEncoding<'a>, &'a str)>
+{
+    s.split(',').rev().map(|seg| {
+        let token = trim_ows(seg);
+        (ContentEncoding::new(token), token)
+    })
+}
+
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), paired with the byte range of each
+/// layer's token within the string.
+///
+/// Ranges exclude surrounding whitespace, so `&s[range]` is the token as written. An empty
+/// element yields an empty range.
+pub fn content_encodings_spanned<'a>(s: &'a str)
+    -> impl Iterator<Item = (ContentEncoding<'a>, core::ops::Range<usize>)>
+{
+    let base = s.as_ptr() as usize;
+
+    s.split(',').rev().map(move |seg| {
+        let token = trim_ows(seg);
+        let start = token.as_ptr() as usize - base;
+
+        (ContentEncoding::new(token), start..start + token.len())
+    })
+}
+
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), only if it declares at most `max`
+/// layers.
+///
+/// Layers are counted by comma-separated elements, not recognized encodings, so empty and
+/// nonstandard elements count toward the limit. The count is checked up front, before any
+/// layer is parsed.
+pub fn content_encodings_limited<'a>(s: &'a str, max: usize)
+    -> Result<ContentEncodings<'a>, LayerLimitError>
+{
+    let encs = content_encodings(s);
+    let layers = encs.len();
+
+    if layers > max {
+        Err(LayerLimitError { layers, max })
+    } else {
+        Ok(encs)
+    }
+}
+
+/// Check that the given `Content-Encoding` string declares a meaningful set of layers.
+///
+/// This fails if `identity` is combined with any other encoding, with the error
+/// describing whether it's the innermost or outermost layer, if an encoding is
+/// applied more than once, or if a layer is the `*` wildcard or has parameters. The
+/// wildcard and parameters such as `;q=0.5` are only meaningful in `Accept-Encoding`,
+/// which [`accept_encodings`](fn.accept_encodings.html) parses, but can't describe a
+/// coding that was actually applied to the content.
+///
+/// Empty list elements are ignored, and layers are checked in the order they must be
+/// decoded, with the first problem found being returned.
+///
+/// Standard encodings are tracked as they're seen, but each nonstandard encoding is
+/// compared against the layers after it, so the work grows quadratically with the number
+/// of nonstandard layers. Untrusted headers can be bounded first with
+/// [`content_encodings_limited`](fn.content_encodings_limited.html).
+pub fn validate_content_encoding<'a>(s: &'a str) -> Result<(), EncodingError<'a>> {
+    check_layers(s, false, |_| {})
+}
+
+/// Check the layers of the given `Content-Encoding` string, like
+/// [`validate_content_encoding`](fn.validate_content_encoding.html), passing each valid
+/// layer to the given function, outermost first.
+///
+/// In `strict` mode, a layer that isn't a valid token is also an error.
+fn check_layers<'a, F>(s: &'a str, strict: bool, mut layer: F) -> Result<(), EncodingError<'a>>
+    where F: FnMut(ContentEncoding<'a>)
+{
+    // Empty list elements are ignored [RFC7230§7], rather than parsed as identity.
+    let mut tokens = s.split(',').rev().map(trim_ows).filter(|e| !e.is_empty());
+
+    let mut seen = 0u32;
+    let mut has_identity = false;
+    let mut has_other = false;
+
+    while let Some(token) = tokens.next() {
+        if token == "*" {
+            return Err(EncodingError::Wildcard);
+        }
+
+        // Unlike in `Accept-Encoding`, codings in `Content-Encoding` take no parameters,
+        // such as `q` weights [RFC7231§3.1.2.2].
+        if token.contains(';') {
+            return Err(EncodingError::Parameter(token));
+        }
+
+        let enc = if strict {
+            ContentEncoding::new_strict(token).map_err(EncodingError::InvalidToken)?
+        } else {
+            ContentEncoding::new(token)
+        };
+
+        if enc.is_identity() {
+            has_identity = true;
         } else {
-            Err(())
+            has_other = true;
+        }
+
+        // Layers are checked outermost first, so a previous identity layer is outermost,
+        // and an identity layer with no layers after it is innermost.
+        if has_identity && has_other {
+            return Err(if !enc.is_identity() {
+                EncodingError::IdentityOutermost
+            } else if tokens.clone().next().is_none() {
+                EncodingError::IdentityInnermost
+            } else {
+                EncodingError::IdentityCombined
+            });
+        }
+
+        let dup = match enc {
+            ContentEncoding::Std(std) => {
+                let bit = 1 << std as u32;
+                let dup = seen & bit != 0;
+                seen |= bit;
+                dup
+            },
+            // A nonstandard token can only match another nonstandard token, since any
+            // alias of a standard encoding would have parsed as one.
+            ContentEncoding::Other(name) =>
+                tokens.clone().any(|t| t.eq_ignore_ascii_case(name)),
+        };
+
+        if dup {
+            return Err(EncodingError::Duplicate(enc));
         }
+
+        layer(enc);
     }
+
+    Ok(())
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Collect the content encoding layers from the given string, outermost first, only if
+/// every layer is a valid token and the layers pass
+/// [`validate_content_encoding`](fn.validate_content_encoding.html).
+///
+/// Empty list elements are ignored and left out of the result. The header is checked in a
+/// single pass, outermost layer first, so when it has several problems, the one in the
+/// outermost layer is returned. Within a layer, the `*` wildcard is reported first, then
+/// parameters, an invalid token, `identity` combined with other encodings, and finally a
+/// repeated encoding.
+#[cfg(feature = "alloc")]
+pub fn parse_validated<'a>(s: &'a str) -> Result<Vec<ContentEncoding<'a>>, EncodingError<'a>> {
+    let mut encs = Vec::with_capacity(count_commas(s.as_bytes()) + 1);
 
-    #[test]
-    fn test_ce() {
-        use self::StdContentEncoding::*;
-        use self::ContentEncoding::*;
+    check_layers(s, true, |enc| encs.push(enc))?;
 
-        assert_eq!(ContentEncoding::new("br"), Std(Brottli));
-        assert_eq!(ContentEncoding::new("\t\t\rBr  "), Std(Brottli));
-        assert_eq!(ContentEncoding::new("compress"), Std(Compress));
-        assert_eq!(ContentEncoding::new("  COMpress "), Std(Compress));
-        assert_eq!(ContentEncoding::new("deflate"), Std(Deflate));
-        assert_eq!(ContentEncoding::new("\t\n dEFLAte "), Std(Deflate));
-        assert_eq!(ContentEncoding::new("exi"), Std(EfficientXML));
-        assert_eq!(ContentEncoding::new("\tEXI\t"), Std(EfficientXML));
-        assert_eq!(ContentEncoding::new("gzip"), Std(Gzip));
-        assert_eq!(ContentEncoding::new("  \tgZIP"), Std(Gzip));
-        assert_eq!(ContentEncoding::new("identity"), Std(Identity));
-        assert_eq!(ContentEncoding::new("\niDENtiTY\r\r\r "), Std(Identity));
-        assert_eq!(ContentEncoding::new(""), Std(Identity));
-        assert_eq!(ContentEncoding::new("    \t "), Std(Identity));
-        assert_eq!(ContentEncoding::new("pack200-gzip"), Std(Pack200Gzip));
-        assert_eq!(ContentEncoding::new("  PaCK200-GZip "), Std(Pack200Gzip));
-        assert_eq!(ContentEncoding::new("ÆØБД❤"), Other("ÆØБД❤"));
+    Ok(encs)
+}
+
+/// Collect the content encoding layers from the given string, outermost first.
+///
+/// The number of layers is known up front, so the `Vec` is allocated once rather than
+/// grown as layers are collected. This is the same as
+/// [`decode_order`](fn.decode_order.html).
+#[cfg(feature = "alloc")]
+pub fn parse_all<'a>(s: &'a str) -> Vec<ContentEncoding<'a>> {
+    content_encodings(s).collect()
+}
+
+/// Collect the content encoding layers from the given string in the order they must be
+/// decoded: outermost (last applied) first.
+///
+/// This is the order yielded by [`content_encodings`](fn.content_encodings.html), and an
+/// explicitly named alias of [`parse_all`](fn.parse_all.html).
+#[cfg(feature = "alloc")]
+pub fn decode_order<'a>(s: &'a str) -> Vec<ContentEncoding<'a>> {
+    parse_all(s)
+}
+
+/// Collect the content encoding layers from the given string in the order they were
+/// applied: innermost (first applied) first.
+///
+/// This is the order the layers appear in the header, and the reverse of
+/// [`decode_order`](fn.decode_order.html).
+#[cfg(feature = "alloc")]
+pub fn encode_order<'a>(s: &'a str) -> Vec<ContentEncoding<'a>> {
+    content_encodings_applied(s).collect()
+}
+
+/// Split the content encoding layers from the given string into the outermost layers
+/// that can be decoded with the given supported encodings, and the rest.
+///
+/// Both are in decode order, outermost first. The first `Vec` holds layers up to the first
+/// unsupported one, which, along with every layer after it, goes in the second `Vec`,
+/// since inner layers can't be reached without decoding it. Identity layers need no
+/// decoding, so they're always supported, and nonstandard encodings never are.
+#[cfg(feature = "alloc")]
+pub fn partition_supported<'a>(s: &'a str, supported: &[StdContentEncoding])
+    -> (Vec<ContentEncoding<'a>>, Vec<ContentEncoding<'a>>)
+{
+    let mut handled = parse_all(s);
+
+    let split = handled.iter().position(|enc| match *enc {
+        ContentEncoding::Std(StdContentEncoding::Identity) => false,
+        ContentEncoding::Std(std) => !supported.contains(&std),
+        ContentEncoding::Other(_) => true,
+    });
+
+    let rest = handled.split_off(split.unwrap_or(handled.len()));
+
+    (handled, rest)
+}
+
+/// Collect the content encoding layers from the given string, outermost first, into a
+/// `SmallVec`.
+///
+/// Headers with up to 4 layers, which covers nearly all real traffic, are stored inline
+/// without allocating. Longer headers spill onto the heap.
+#[cfg(feature = "smallvec")]
+pub fn parse_all_small<'a>(s: &'a str) -> smallvec::SmallVec<[ContentEncoding<'a>; 4]> {
+    content_encodings(s).collect()
+}
+
+/// Create an iterator over the content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), skipping identity layers.
+///
+/// A header made up of only identity and empty elements yields nothing.
+pub fn effective_encodings<'a>(s: &'a str) -> impl Iterator<Item = ContentEncoding<'a>> {
+    content_encodings(s).filter(|e| !e.is_identity())
+}
+
+/// Count the content encoding layers in the given `Content-Encoding` string.
+///
+/// Every comma-separated element counts as a layer, including empty elements, which parse
+/// as identity.
+pub fn count_layers(s: &str) -> usize {
+    content_encodings(s).len()
+}
+
+/// Count the content encoding layers in the given `Content-Encoding` string, excluding
+/// identity layers.
+pub fn count_effective_layers(s: &str) -> usize {
+    effective_encodings(s).count()
+}
+
+/// Create an iterator over runs of consecutive identical content encoding layers from the
+/// given string, yielding each encoding with the number of times it's repeated.
+///
+/// Runs are yielded outermost first, like
+/// [`content_encodings`](fn.content_encodings.html), and a run longer than 1 usually
+/// indicates redundant compression. Nonstandard encodings are compared ignoring case, and
+/// the outermost layer of each run is the one yielded.
+pub fn dedup_consecutive<'a>(s: &'a str) -> impl Iterator<Item = (ContentEncoding<'a>, usize)> {
+    let mut encs = content_encodings(s).peekable();
+
+    core::iter::from_fn(move || {
+        let enc = encs.next()?;
+        let mut count = 1;
+
+        while encs.next_if(|e| same_coding(e, &enc)).is_some() {
+            count += 1;
+        }
+
+        Some((enc, count))
+    })
+}
+
+/// Count the distinct effective encodings in the given `Content-Encoding` string.
+///
+/// Identity and empty layers are ignored, and repeated layers are counted once, with
+/// standard encodings compared by scheme and nonstandard encodings compared ignoring
+/// ASCII case. Like [`validate_content_encoding`](fn.validate_content_encoding.html), each
+/// nonstandard layer is compared against the layers after it.
+pub fn distinct_coding_count(s: &str) -> usize {
+    let mut encs = content_encodings(s);
+    let mut seen = 0u32;
+    let mut count = 0;
+
+    while let Some(enc) = encs.next() {
+        let new = match enc {
+            ContentEncoding::Std(StdContentEncoding::Identity) => false,
+            ContentEncoding::Std(std) => {
+                let bit = 1 << std as u32;
+                let new = seen & bit == 0;
+                seen |= bit;
+                new
+            },
+            // Only the last occurrence of a nonstandard encoding is counted.
+            ContentEncoding::Other(_) => !encs.clone().any(|e| same_coding(&e, &enc)),
+        };
+
+        if new {
+            count += 1;
+        }
     }
 
-    #[test]
-    fn test_ces() {
-        use self::StdContentEncoding::*;
-        use self::ContentEncoding::*;
+    count
+}
 
-        let mut ce = content_encodings("deflate, br, identity");
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert_eq!(ce.next().unwrap(), Std(Brottli));
-        assert_eq!(ce.next().unwrap(), Std(Deflate));
-        assert!(ce.next().is_none());
+/// Check if the decode order of the given `Content-Encoding` string matters.
+///
+/// This is false only when the header has at most one effective layer, ignoring identity
+/// and empty layers, in which case there's nothing to order and the header can be
+/// normalized freely, such as by a cache.
+pub fn order_significant(s: &str) -> bool {
+    effective_encodings(s).nth(1).is_some()
+}
 
-        let mut ce = content_encodings("identity");
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert!(ce.next().is_none());
+/// Check if the given `Content-Encoding` strings declare the same effective layers.
+///
+/// Identity and empty layers are ignored, and the remaining layers must match in decode
+/// order, with nonstandard encodings compared ignoring case. Headers that differ only in
+/// casing, whitespace, aliases, or identity layers are equivalent.
+pub fn headers_equivalent(a: &str, b: &str) -> bool {
+    let mut a = effective_encodings(a);
+    let mut b = effective_encodings(b);
 
-        let mut ce = content_encodings("");
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert!(ce.next().is_none());
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return true,
+            (Some(x), Some(y)) if same_coding(&x, &y) => {},
+            _ => return false,
+        }
+    }
+}
 
-        let mut ce = content_encodings("\t\t,,            ,     ,");
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert_eq!(ce.next().unwrap(), Std(Identity));
-        assert!(ce.next().is_none());
+/// Check if the given `Content-Encoding` strings declare the same effective layers in any
+/// order.
+///
+/// Like [`headers_equivalent`](fn.headers_equivalent.html), identity and empty layers are
+/// ignored and nonstandard encodings are compared ignoring case, but the layers are
+/// compared as multisets, so repeats matter: `gzip, br` matches `br, gzip` but not
+/// `gzip, br, gzip`. Each layer is counted in both headers, so the work grows
+/// quadratically with the number of layers.
+pub fn same_coding_set(a: &str, b: &str) -> bool {
+    fn occurrences(s: &str, enc: &ContentEncoding) -> usize {
+        parse_layers(s).filter(|e| same_coding(e, enc)).count()
+    }
 
-        let mut ce = content_encodings("Br, exi,pack200-GZip   ");
-        assert_eq!(ce.next().unwrap(), Std(Pack200Gzip));
-        assert_eq!(ce.next().unwrap(), Std(EfficientXML));
-        assert_eq!(ce.next().unwrap(), Std(Brottli));
-        assert!(ce.next().is_none());
+    // With equal sizes, matching counts for every layer of one header leave no room for
+    // other layers in the other.
+    count_effective_layers(a) == count_effective_layers(b) &&
+        parse_layers(a).filter(|e| !e.is_identity())
+            .all(|enc| occurrences(a, &enc) == occurrences(b, &enc))
+}
 
-        let mut ce = content_encodings("\t\t\t   gzip");
-        assert_eq!(ce.next().unwrap(), Std(Gzip));
-        assert!(ce.next().is_none());
+/// Check if the given encodings are the same, ignoring the case of nonstandard names.
+fn same_coding(a: &ContentEncoding, b: &ContentEncoding) -> bool {
+    match (*a, *b) {
+        (ContentEncoding::Std(a), ContentEncoding::Std(b)) => a == b,
+        (ContentEncoding::Other(a), ContentEncoding::Other(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
 
-        let mut ce = content_encodings("\tabc\t\t, def  ");
-        assert_eq!(ce.next().unwrap(), Other("def"));
-        assert_eq!(ce.next().unwrap(), Other("abc"));
-        assert!(ce.next().is_none());
+/// Retrieve the innermost content encoding layer from the given string.
+///
+/// The innermost layer is the first one that was applied to the content, which is listed
+/// first in the header and yielded last by
+/// [`content_encodings`](fn.content_encodings.html). It's the final layer to decode, so
+/// it reveals the format of the underlying content. For `gzip, br` this is `gzip`. An empty
+/// header has a single identity layer.
+pub fn innermost_encoding<'a>(s: &'a str) -> ContentEncoding<'a> {
+    // Splitting always yields at least one element.
+    ContentEncoding::new(s.split(',').next().unwrap())
+}
+
+/// Retrieve the outermost content encoding layer from the given string.
+///
+/// The outermost layer is the last one that was applied to the content, which is listed
+/// last in the header and yielded first by
+/// [`content_encodings`](fn.content_encodings.html). It's the first layer to decode, so
+/// it selects the decoder to start with. For `gzip, br` this is `br`. An empty header has a
+/// single identity layer.
+pub fn outermost_encoding<'a>(s: &'a str) -> ContentEncoding<'a> {
+    // Splitting always yields at least one element.
+    ContentEncoding::new(s.rsplit(',').next().unwrap())
+}
+
+/// Split the outermost content encoding layer from the given string, returning it along
+/// with the rest of the header.
+///
+/// The rest is a slice of the header with the last element and its comma removed, so it
+/// can be passed downstream, or peeled again, without being collected. For a single layer,
+/// the rest is empty. This returns `None` only when the header is empty, so repeated
+/// peeling stops after the innermost layer.
+///
+/// Like [`content_encodings`](fn.content_encodings.html), an element of only whitespace is
+/// an identity layer, so `" , a"` peels as `a` and then identity. An element with no
+/// characters at all can't be told apart from the end of the header, though, so an empty
+/// innermost element, as in `",a"`, isn't peeled.
+pub fn strip_outer<'a>(s: &'a str) -> Option<(ContentEncoding<'a>, &'a str)> {
+    if s.is_empty() {
+        return None;
+    }
+
+    Some(match s.rfind(',') {
+        Some(i) => (ContentEncoding::new(&s[i + 1..]), &s[..i]),
+        None => (ContentEncoding::new(s), ""),
+    })
+}
+
+/// Check if the given `Content-Encoding` string declares the given standard encoding in
+/// any layer.
+///
+/// Scanning stops at the first match. Like
+/// [`content_encodings`](fn.content_encodings.html), empty elements parse as identity.
+pub fn header_contains(s: &str, enc: StdContentEncoding) -> bool {
+    s.split(',').any(|e| ContentEncoding::new(e) == ContentEncoding::Std(enc))
+}
+
+/// Create an iterator over the names of the nonstandard content encoding layers in the
+/// given string, skipping standard layers.
+///
+/// Names are yielded outermost first, like
+/// [`content_encodings`](fn.content_encodings.html), without surrounding whitespace and in
+/// their original casing. Repeated names are yielded each time they appear.
+pub fn other_codings(s: &str) -> impl Iterator<Item = &str> {
+    content_encodings(s).filter_map(|enc| match enc {
+        ContentEncoding::Other(name) => Some(name),
+        ContentEncoding::Std(_) => None,
+    })
+}
+
+/// Check if the given `Content-Encoding` string declares the given nonstandard encoding in
+/// any layer, ignoring case.
+///
+/// Only nonstandard layers are compared, so this is always false for the name of a
+/// standard encoding, such as `gzip`.
+pub fn header_contains_other(s: &str, name: &str) -> bool {
+    s.split(',').any(|e| match ContentEncoding::new(e) {
+        ContentEncoding::Other(other) => other.eq_ignore_ascii_case(name),
+        ContentEncoding::Std(_) => false,
+    })
+}
+
+/// Check if every layer of the given `Content-Encoding` string is identity, so there's
+/// nothing to decode.
+///
+/// Empty, whitespace-only, and all-comma headers are identity only. Checking stops at the
+/// first other layer, without scanning the rest of the header.
+pub fn is_identity_only(s: &str) -> bool {
+    s.split(',').all(|e| ContentEncoding::new(e).is_identity())
+}
+
+/// Check if the given `Content-Encoding` string stacks two or more compressing encodings,
+/// such as `gzip, gzip` or `br, gzip`.
+///
+/// Compressing already compressed data wastes work and is almost always a bug. The
+/// compressing encodings are those with a
+/// [`compression_class`](enum.StdContentEncoding.html#method.compression_class) other than
+/// `None`, including the dictionary-compressed `dcb` and `dcz`. Other layers, including
+/// identity, `aes128gcm`, and nonstandard encodings, are ignored.
+pub fn has_redundant_compression(s: &str) -> bool {
+    let mut compressing = content_encodings(s).filter(|enc| match *enc {
+        ContentEncoding::Std(enc) => enc.compression_class() != CompressionClass::None,
+        ContentEncoding::Other(_) => false,
+    });
+
+    compressing.next().is_some() && compressing.next().is_some()
+}
+
+/// Create an iterator over content encoding layers from the given string, in the order
+/// they were applied.
+///
+/// This yields the same layers as [`content_encodings`](fn.content_encodings.html) but in
+/// reverse, following the order they appear in the header: the innermost layer is
+/// yielded first and the outermost layer is yielded last.
+pub fn content_encodings_applied<'a>(s: &'a str) -> core::iter::Rev<ContentEncodings<'a>> {
+    content_encodings(s).rev()
+}
+
+/// Create an iterator over content encoding layers from an optional `Content-Encoding`
+/// string, distinguishing a missing header from an empty one.
+///
+/// A missing header (`None`) yields nothing, while an empty header yields a single
+/// identity layer, like [`content_encodings`](fn.content_encodings.html).
+pub fn content_encodings_opt<'a>(s: Option<&'a str>)
+    -> impl Iterator<Item = ContentEncoding<'a>>
+{
+    s.into_iter().flat_map(content_encodings)
+}
+
+/// Create an iterator over content encoding layers from a header value that's been split
+/// into parts, without joining them into a single string.
+///
+/// Parts are parsed as if joined by commas, like multiple field lines of the same header
+/// [RFC7230§3.2.2], and a comma at the edge of a part merges with the boundary next to it,
+/// so `["gzip", "br"]`, `["gzip, ", "br"]`, and `["gzip", ", br"]` all parse like
+/// `gzip, br`. Layers are yielded outermost first, like
+/// [`content_encodings`](fn.content_encodings.html), and no parts yield no layers.
+pub fn content_encodings_from_parts<'a>(parts: &'a [&'a str])
+    -> impl Iterator<Item = ContentEncoding<'a>>
+{
+    let last = parts.len().saturating_sub(1);
+
+    parts.iter().enumerate().rev().flat_map(move |(i, &part)| {
+        let mut part = trim_ows(part);
+
+        if i > 0 && part.starts_with(',') {
+            part = &part[1..];
+        }
+
+        if i < last && part.ends_with(',') {
+            part = &part[..part.len() - 1];
+        }
+
+        part.split(',').rev().map(ContentEncoding::new)
+    })
+}
+
+/// Create an iterator over content encoding layers from multiple field lines of the same
+/// `Content-Encoding` header.
+///
+/// Lines are parsed as if combined into one value by joining them with commas
+/// [RFC7230§3.2.2], without allocating. Layers are yielded outermost first across all
+/// lines, like [`content_encodings`](fn.content_encodings.html), so the last line's last
+/// layer comes first and `["gzip", "br"]` yields `br` then `gzip`. Unlike
+/// [`content_encodings_from_parts`](fn.content_encodings_from_parts.html), each line is a
+/// whole value, and no lines yield no layers.
+///
+/// Lines are visited from the last, so the iterator over them must be double-ended, as it
+/// is for arrays, slices, and `Vec`s.
+pub fn content_encodings_multi<'a, I>(lines: I) -> impl Iterator<Item = ContentEncoding<'a>>
+    where I: IntoIterator<Item = &'a str>,
+          I::IntoIter: DoubleEndedIterator
+{
+    lines.into_iter().rev().flat_map(content_encodings)
+}
+
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), skipping empty elements.
+///
+/// By default, empty and whitespace-only elements, such as those around the extra commas
+/// in `gzip,,br,`, parse as identity. This instead ignores them entirely [RFC7230§7], so
+/// an empty header yields nothing, while an explicit `identity` is still yielded.
+pub fn content_encodings_skip_empty<'a>(s: &'a str)
+    -> impl DoubleEndedIterator<Item = ContentEncoding<'a>>
+{
+    s.split(',').rev()
+        .map(trim_ows)
+        .filter(|e| !e.is_empty())
+        .map(ContentEncoding::new)
+}
+
+/// Create an iterator over content encoding layers from the given string, like
+/// [`content_encodings`](fn.content_encodings.html), marking the innermost layer.
+///
+/// Each layer is paired with a flag that's true only for the innermost layer, which was
+/// applied first and is yielded last. Its encoding is the data's underlying format. Every
+/// header has at least one layer, so exactly one layer is marked.
+pub fn content_encodings_marked<'a>(s: &'a str)
+    -> impl Iterator<Item = (ContentEncoding<'a>, bool)>
+{
+    let encs = content_encodings(s);
+    let layers = encs.len();
+
+    encs.enumerate().map(move |(i, enc)| (enc, i + 1 == layers))
+}
+
+/// Iterator over content encoding layers, created by
+/// [`content_encodings`](fn.content_encodings.html).
+///
+/// Layers are yielded outermost first, and `next_back` yields from the innermost layer.
+///
+/// Creating the iterator scans the whole header once to count its commas, so the exact
+/// remaining length is always known. Layers themselves are parsed lazily from the end of
+/// the string, so yielding or peeking at the outermost layer only parses that layer's
+/// element.
+///
+/// Once exhausted, the iterator keeps returning `None`, as marked by its `FusedIterator`
+/// implementation.
+///
+/// This is built on [`RawContentEncodings`](struct.RawContentEncodings.html), with each
+/// nonstandard name mapped back to its place in the header string.
+#[derive(Clone, Debug)]
+pub struct ContentEncodings<'a> {
+    raw: RawContentEncodings<'a>,
+    header: &'a str,
+    remaining: usize,
+}
+
+impl<'a> Iterator for ContentEncodings<'a> {
+    type Item = ContentEncoding<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let enc = self.raw.next()?;
+        self.remaining -= 1;
+
+        Some(ContentEncoding::from_raw(self.header, enc))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for ContentEncodings<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let enc = self.raw.next_back()?;
+        self.remaining -= 1;
+
+        Some(ContentEncoding::from_raw(self.header, enc))
+    }
+}
+
+impl<'a> ExactSizeIterator for ContentEncodings<'a> {}
+
+impl<'a> core::iter::FusedIterator for ContentEncodings<'a> {}
+
+/// Create an iterator over content encoding layers from the given string, leniently
+/// splitting off any parameters that follow a coding.
+///
+/// Parameters aren't part of the `Content-Encoding` grammar, but some deployments send
+/// values like `gzip; level=9`. Rather than parsing such an element as a nonstandard
+/// encoding, this recognizes the base coding and yields the parameters separately. Commas
+/// within quoted-string parameter values don't separate layers. Layers are yielded
+/// outermost first, like [`content_encodings`](fn.content_encodings.html).
+///
+/// A backslash escapes the following character wherever it appears, as it does within a
+/// quoted-string. Backslashes aren't otherwise allowed in the header, so this only
+/// affects malformed values. The string is scanned once up front to find whether it ends
+/// within a quoted string, after which all layers are split off in a single backward pass.
+pub fn content_encodings_lenient<'a>(s: &'a str) -> LenientContentEncodings<'a> {
+    let mut quoted = false;
+    let mut escaped = false;
+
+    // Find whether the string ends within a quoted string, so it can be split from the
+    // end.
+    for b in s.bytes() {
+        if escaped {
+            escaped = false;
+        } else if b == b'\\' {
+            escaped = true;
+        } else if b == b'"' {
+            quoted = !quoted;
+        }
+    }
+
+    LenientContentEncodings {
+        rest: Some(s),
+        quoted,
+    }
+}
+
+/// Iterator over content encoding layers with parameters, created by
+/// [`content_encodings_lenient`](fn.content_encodings_lenient.html).
+#[derive(Clone, Debug)]
+pub struct LenientContentEncodings<'a> {
+    rest: Option<&'a str>,
+    /// Whether the end of `rest` is within a quoted string.
+    quoted: bool,
+}
+
+impl<'a> Iterator for LenientContentEncodings<'a> {
+    type Item = ParsedEncoding<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let s = self.rest?;
+        let bytes = s.as_bytes();
+
+        for i in (0..bytes.len()).rev() {
+            match bytes[i] {
+                b'"' if !is_escaped(bytes, i) => self.quoted = !self.quoted,
+                b',' if !self.quoted && !is_escaped(bytes, i) => {
+                    self.rest = Some(&s[..i]);
+                    return Some(ParsedEncoding::new(&s[i + 1..]));
+                },
+                _ => {},
+            }
+        }
+
+        self.rest = None;
+        Some(ParsedEncoding::new(s))
+    }
+}
+
+/// Check if the byte at the given index is escaped by an odd number of backslashes.
+fn is_escaped(s: &[u8], i: usize) -> bool {
+    s[..i].iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1
+}
+
+/// Content encoding layer with any parameters that followed it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct ParsedEncoding<'a> {
+    /// Base content encoding.
+    pub encoding: ContentEncoding<'a>,
+    /// Parameters following the first `;`, with surrounding whitespace removed.
+    pub params: Option<&'a str>,
+}
+
+impl<'a> ParsedEncoding<'a> {
+    /// Parse a new `ParsedEncoding` from the given list element.
+    pub fn new(s: &'a str) -> Self {
+        let mut parts = s.splitn(2, ';');
+
+        ParsedEncoding {
+            encoding: ContentEncoding::new(parts.next().unwrap()),
+            params: parts.next().map(trim_ows),
+        }
+    }
+}
+
+/// Create an iterator over transfer coding layers from the given string in [the
+/// form](https://tools.ietf.org/html/rfc7230#section-3.3.1) used by the `Transfer-Encoding`
+/// header field.
+///
+/// Like [`content_encodings`](fn.content_encodings.html), layers are yielded outermost
+/// first. Since `chunked` must be the final coding applied to a message body, it's always
+/// the first layer yielded from a valid header.
+pub fn transfer_encodings<'a>(s: &'a str) -> TransferEncodings<'a> {
+    TransferEncodings(s.split(','))
+}
+
+/// Iterator over transfer coding layers, created by
+/// [`transfer_encodings`](fn.transfer_encodings.html).
+///
+/// Layers are yielded outermost first, and `next_back` yields from the innermost layer.
+#[derive(Clone, Debug)]
+pub struct TransferEncodings<'a>(core::str::Split<'a, char>);
+
+impl<'a> Iterator for TransferEncodings<'a> {
+    type Item = TransferEncoding<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(TransferEncoding::new)
+    }
+}
+
+impl<'a> DoubleEndedIterator for TransferEncodings<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next().map(TransferEncoding::new)
+    }
+}
+
+/// Create an iterator over content encoding layers from the given raw header bytes.
+///
+/// This behaves like [`content_encodings`](fn.content_encodings.html), but doesn't require
+/// the header value to be valid UTF-8. Nonstandard encodings are yielded as byte slices.
+///
+/// This is the primitive parser, which suits header values from byte-oriented parsers
+/// such as `httparse`: [`content_encodings`](fn.content_encodings.html) is built on it, and
+/// [`RawContentEncoding::to_utf8`](enum.RawContentEncoding.html#method.to_utf8) converts a
+/// layer when a string is needed.
+pub fn content_encodings_bytes<'a>(s: &'a [u8]) -> RawContentEncodings<'a> {
+    RawContentEncodings { rest: s, done: false }
+}
+
+/// Iterator over content encoding layers in raw header bytes, created by
+/// [`content_encodings_bytes`](fn.content_encodings_bytes.html).
+///
+/// Layers are yielded outermost first, and `next_back` yields from the innermost layer.
+#[derive(Clone, Debug)]
+pub struct RawContentEncodings<'a> {
+    /// Elements not yet yielded, between the last comma found at each end.
+    rest: &'a [u8],
+    /// Whether the final element has been yielded.
+    done: bool,
+}
+
+impl<'a> RawContentEncodings<'a> {
+    /// Yield the final element once no commas remain.
+    fn finish(&mut self) -> Option<RawContentEncoding<'a>> {
+        if self.done {
+            None
+        } else {
+            self.done = true;
+            Some(RawContentEncoding::new(self.rest))
+        }
+    }
+}
+
+impl<'a> Iterator for RawContentEncodings<'a> {
+    type Item = RawContentEncoding<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match rfind_comma(self.rest) {
+            Some(i) => {
+                let s = &self.rest[i + 1..];
+                self.rest = &self.rest[..i];
+                Some(RawContentEncoding::new(s))
+            },
+            None => self.finish(),
+        }
+    }
+}
+
+impl<'a> DoubleEndedIterator for RawContentEncodings<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match find_comma(self.rest) {
+            Some(i) => {
+                let s = &self.rest[..i];
+                self.rest = &self.rest[i + 1..];
+                Some(RawContentEncoding::new(s))
+            },
+            None => self.finish(),
+        }
+    }
+}
+
+impl<'a> core::iter::FusedIterator for RawContentEncodings<'a> {}
+
+/// Create an iterator over content encoding layers from the given `http` crate header
+/// value.
+///
+/// Header values may contain opaque non-ASCII bytes that aren't valid UTF-8, so this
+/// parses with [`content_encodings_bytes`](fn.content_encodings_bytes.html) and yields
+/// any such bytes as part of a nonstandard encoding rather than failing.
+#[cfg(feature = "http")]
+pub fn content_encodings_header(value: &http::HeaderValue) -> RawContentEncodings<'_> {
+    content_encodings_bytes(value.as_bytes())
+}
+
+/// Count the commas in the given bytes.
+fn count_commas(s: &[u8]) -> usize {
+    // Counting into a byte within chunks short enough that it can't overflow lets the
+    // loop vectorize.
+    s.chunks(255)
+        .map(|c| c.iter().fold(0u8, |n, &b| n + (b == b',') as u8) as usize)
+        .sum()
+}
+
+/// Find the index of the first comma in the given bytes.
+fn find_comma(s: &[u8]) -> Option<usize> {
+    let mut start = 0;
+
+    // Checking whole chunks for a comma, without stopping early, lets the loop vectorize.
+    for chunk in s.chunks(64) {
+        if chunk.iter().fold(false, |found, &b| found | (b == b',')) {
+            return chunk.iter().position(|&b| b == b',').map(|i| start + i);
+        }
+
+        start += chunk.len();
+    }
+
+    None
+}
+
+/// Find the index of the last comma in the given bytes, like `find_comma`.
+fn rfind_comma(s: &[u8]) -> Option<usize> {
+    let mut end = s.len();
+
+    for chunk in s.rchunks(64) {
+        end -= chunk.len();
+
+        if chunk.iter().fold(false, |found, &b| found | (b == b',')) {
+            return chunk.iter().rposition(|&b| b == b',').map(|i| end + i);
+        }
+    }
+
+    None
+}
+
+/// Serialize content encoding layers back into a `Content-Encoding` header value.
+///
+/// Layers are taken in the order yielded by [`content_encodings`](fn.content_encodings.html),
+/// outermost first, and written in the order they were applied, joined by `", "`. An empty
+/// iterator produces an empty string, in which case the header should be omitted.
+#[cfg(feature = "alloc")]
+pub fn encode_content_encodings<'a, I>(encs: I) -> String
+    where I: IntoIterator<Item = ContentEncoding<'a>>
+{
+    use alloc::fmt::Write;
+
+    let layers = encs.into_iter().collect::<Vec<_>>();
+    let mut s = String::new();
+
+    for (i, enc) in layers.iter().rev().enumerate() {
+        if i > 0 {
+            s.push_str(", ");
+        }
+
+        write!(s, "{}", enc).unwrap();
+    }
+
+    s
+}
+
+/// Normalize the given `Content-Encoding` string into a canonical header value.
+///
+/// Identity and empty layers are removed, and the remaining layers are written as ASCII
+/// lowercase tokens in the order they were applied, joined by `", "`. Headers that differ
+/// only in casing, whitespace, aliases, or identity layers produce the same string, which
+/// makes this suitable as a cache key.
+#[cfg(feature = "alloc")]
+pub fn canonical_header(s: &str) -> String {
+    let mut header = String::new();
+
+    for enc in content_encodings_applied(s).filter(|e| !e.is_identity()) {
+        if !header.is_empty() {
+            header.push_str(", ");
+        }
+
+        header.extend(enc.canonical_name().chars().map(|c| c.to_ascii_lowercase()));
+    }
+
+    header
+}
+
+/// Compute a stable cache key component for the effective encoding of the given
+/// `Content-Encoding` string, such as for responses varying on it.
+///
+/// This is the [`canonical_header`](fn.canonical_header.html) of the string, except that a
+/// header with no effective encoding, whether empty or only identity, produces the literal
+/// `"identity"` rather than an empty string.
+#[cfg(feature = "alloc")]
+pub fn cache_key_component(s: &str) -> String {
+    let key = canonical_header(s);
+
+    if key.is_empty() {
+        String::from("identity")
+    } else {
+        key
+    }
+}
+
+/// Create an iterator over the acceptable content encodings and their quality values from
+/// the given string in [the form](https://tools.ietf.org/html/rfc7231#section-5.3.4) used
+/// by the `Accept-Encoding` header field.
+///
+/// Encodings are yielded in the order they appear, paired with their `q` weight, which
+/// defaults to `1.0` when absent. A weight that doesn't follow the [qvalue
+/// grammar](https://tools.ietf.org/html/rfc7231#section-5.3.1) but is still a number, such
+/// as `2`, `-1`, or `0.1234`, is clamped to the range 0 to 1, and one that isn't, such as
+/// `abc` or `nan`, is treated as `0.0` ("not acceptable"). The `*` wildcard is yielded as
+/// `ContentEncoding::Other("*")`, and empty list elements are skipped.
+///
+/// For other treatments of malformed weights, see
+/// [`accept_encodings_recovering`](fn.accept_encodings_recovering.html), and
+/// [`accept_encodings_strict`](fn.accept_encodings_strict.html), whose errors can be
+/// treated as `0.0` to refuse any element with an out-of-range weight.
+pub fn accept_encodings<'a>(s: &'a str) -> AcceptEncodings<'a> {
+    AcceptEncodings(s.split(','))
+}
+
+/// Iterator over acceptable content encodings, created by
+/// [`accept_encodings`](fn.accept_encodings.html).
+#[derive(Clone, Debug)]
+pub struct AcceptEncodings<'a>(core::str::Split<'a, char>);
+
+impl<'a> Iterator for AcceptEncodings<'a> {
+    type Item = (ContentEncoding<'a>, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((enc, q)) = accept_element(self.0.next()?) {
+                return Some((enc, q.map_or(1.0, |q| recover_qvalue(q, 0.0))));
+            }
+        }
+    }
+}
+
+/// Create an iterator over the acceptable content encodings and their quality values from
+/// the given `Accept-Encoding` string, like
+/// [`accept_encodings`](fn.accept_encodings.html), recovering from malformed weights.
+///
+/// Real-world headers sometimes carry weights outside the qvalue grammar. A weight that's
+/// still a number, such as `1.5`, `-0.1`, or `0.1234`, is clamped to the range 0 to 1,
+/// and one that isn't, such as `abc`, `nan`, or an empty value, is replaced by the given
+/// `malformed` value, typically `1.0`. Absent weights default to `1.0` as usual.
+pub fn accept_encodings_recovering<'a>(s: &'a str, malformed: f32)
+    -> impl Iterator<Item = (ContentEncoding<'a>, f32)>
+{
+    s.split(',').filter_map(accept_element)
+        .map(move |(enc, q)| (enc, q.map_or(1.0, |q| recover_qvalue(q, malformed))))
+}
+
+/// Parse a quality value, clamping a number outside the qvalue grammar to the range 0 to 1
+/// and replacing anything else with the given `malformed` value.
+fn recover_qvalue(q: &str, malformed: f32) -> f32 {
+    match qvalue(q) {
+        Some(q) => q,
+        None => match q.parse::<f32>() {
+            Ok(q) if !q.is_nan() => q.clamp(0.0, 1.0),
+            _ => malformed,
+        },
+    }
+}
+
+/// Create an iterator over the acceptable content encodings and their quality values from
+/// the given `Accept-Encoding` string, like
+/// [`accept_encodings`](fn.accept_encodings.html), rejecting malformed weights.
+///
+/// A weight that doesn't follow the [qvalue
+/// grammar](https://tools.ietf.org/html/rfc7231#section-5.3.1) yields an error in place of
+/// its element, and the remaining elements are still yielded.
+pub fn accept_encodings_strict<'a>(s: &'a str)
+    -> impl Iterator<Item = Result<(ContentEncoding<'a>, f32), InvalidQvalueError<'a>>>
+{
+    s.split(',').filter_map(accept_element).map(|(enc, q)| match q {
+        Some(q) => qvalue(q).map(|q| (enc, q)).ok_or(InvalidQvalueError { value: q }),
+        None => Ok((enc, 1.0)),
+    })
+}
+
+/// Split an `Accept-Encoding` list element into its encoding and its raw `q` weight, if
+/// any, or return `None` for an empty element.
+fn accept_element<'a>(s: &'a str) -> Option<(ContentEncoding<'a>, Option<&'a str>)> {
+    let mut parts = s.split(';');
+
+    // The first part always exists, even for an empty element.
+    let enc = trim_ows(parts.next().unwrap());
+
+    // Empty list elements are ignored [RFC7230§7].
+    if enc.is_empty() {
+        None
+    } else {
+        Some((ContentEncoding::new(enc), qvalue_param(parts)))
+    }
+}
+
+/// Collect the acceptable content encodings from the given `Accept-Encoding` string, most
+/// preferred first.
+///
+/// Encodings are sorted by descending quality value, with ties kept in header order.
+/// Encodings with a quality value of 0 are "not acceptable" and are left out.
+#[cfg(feature = "alloc")]
+pub fn preferred_encodings<'a>(s: &'a str) -> Vec<(ContentEncoding<'a>, f32)> {
+    let mut encs = accept_encodings(s).filter(|&(_, q)| q > 0.0).collect::<Vec<_>>();
+
+    // This is a stable sort.
+    encs.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    encs
+}
+
+/// Check if the given `Accept-Encoding` string accepts the given content encoding, by the
+/// rules of [RFC7231§5.3.4](https://tools.ietf.org/html/rfc7231#section-5.3.4).
+///
+/// An encoding listed with a quality value of 0 is not acceptable, and one listed with any
+/// other value is. An encoding that isn't listed takes the value of the `*` wildcard, if
+/// present, so `"*, identity;q=0"` accepts anything but identity. Otherwise, only identity
+/// is acceptable when not listed, and so is the only encoding accepted by an empty header.
+///
+/// When an encoding is listed more than once, its first entry is used.
+pub fn accepts(encodings: &str, enc: ContentEncoding) -> bool {
+    match listed_qvalue(encodings, enc) {
+        Some(q) => q > 0.0,
+        None => enc.is_identity(),
+    }
+}
+
+/// Select the best content encoding from the given supported encodings for a response to a
+/// request with the given `Accept-Encoding` string.
+///
+/// This returns the supported encoding with the highest quality value that the request
+/// [`accepts`](fn.accepts.html), with ties going to the encoding listed first in
+/// `supported`, so it should be ordered by the server's preference. Identity is always
+/// available, even if not in `supported`, and is chosen when no other encoding is
+/// acceptable or when it has a higher quality value. When identity isn't listed and the
+/// header has no `*` wildcard, it's acceptable but least preferred.
+///
+/// This returns `None` only when identity is explicitly excluded, such as by
+/// `identity;q=0` or `*;q=0`, and no supported encoding is acceptable, in which case the
+/// server may respond with 406 (Not Acceptable).
+pub fn negotiate(accept: &str, supported: &[StdContentEncoding]) -> Option<StdContentEncoding> {
+    let identity = core::iter::once(StdContentEncoding::Identity);
+    let mut best: Option<(StdContentEncoding, f32)> = None;
+
+    for enc in supported.iter().cloned().chain(identity) {
+        let q = match listed_qvalue(accept, ContentEncoding::Std(enc)) {
+            Some(q) if q > 0.0 => q,
+            None if enc == StdContentEncoding::Identity => 0.0,
+            _ => continue,
+        };
+
+        if best.is_none_or(|(_, b)| q > b) {
+            best = Some((enc, q));
+        }
+    }
+
+    best.map(|(enc, _)| enc)
+}
+
+/// Retrieve the quality value given to the given content encoding by the given
+/// `Accept-Encoding` string, from its first entry or else the `*` wildcard, or `None` if
+/// neither is present.
+fn listed_qvalue(encodings: &str, enc: ContentEncoding) -> Option<f32> {
+    let enc = enc.canonicalize();
+    let mut wildcard = None;
+
+    for (e, q) in accept_encodings(encodings) {
+        if same_coding(&e, &enc) {
+            return Some(q);
+        }
+
+        if wildcard.is_none() && e == ContentEncoding::Other("*") {
+            wildcard = Some(q);
+        }
+    }
+
+    wildcard
+}
+
+/// Extract the raw quality value from the given parameters, if present.
+///
+/// A `q` parameter without a value is extracted as an empty string.
+fn qvalue_param<'a, I: Iterator<Item = &'a str>>(params: I) -> Option<&'a str> {
+    for param in params {
+        let mut kv = param.splitn(2, '=');
+        let key = trim_ows(kv.next().unwrap());
+
+        if key.eq_ignore_ascii_case("q") {
+            return Some(kv.next().map_or("", trim_ows));
+        }
+    }
+
+    None
+}
+
+/// Parse a quality value, which is a number from 0 to 1 with at most 3 decimal places
+/// [RFC7231§5.3.1].
+fn qvalue(s: &str) -> Option<f32> {
+    let (int, frac) = match s.find('.') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, ""),
+    };
+
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    // Pad the fraction out to thousandths.
+    let millis = frac.bytes().chain(core::iter::repeat(b'0')).take(3)
+        .fold(0, |n, b| n * 10 + u32::from(b - b'0'));
+
+    match int {
+        "0" => Some(millis as f32 / 1000.0),
+        "1" if millis == 0 => Some(1.0),
+        _ => None,
+    }
+}
+
+/// Builder for a `Content-Encoding` header value from typed layers.
+///
+/// Layers are pushed in the order they were applied, innermost first, and written joined
+/// by `", "`.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ContentEncodingBuilder {
+    header: String,
+}
+
+#[cfg(feature = "alloc")]
+impl ContentEncodingBuilder {
+    /// Create a new builder with no layers.
+    pub fn new() -> Self {
+        ContentEncodingBuilder::default()
+    }
+
+    /// Append a standard encoding layer.
+    pub fn push(&mut self, enc: StdContentEncoding) -> &mut Self {
+        self.push_token(enc.token());
+        self
+    }
+
+    /// Append a nonstandard encoding layer with the given name.
+    ///
+    /// This fails if the name isn't a valid
+    /// [token](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+    pub fn push_other<'b>(&mut self, name: &'b str)
+        -> Result<&mut Self, InvalidTokenError<'b>>
+    {
+        if !is_token(name) {
+            return Err(InvalidTokenError {
+                token: name,
+                kind: ParseEncodingErrorKind::InvalidToken,
+            });
+        }
+
+        self.push_token(name);
+        Ok(self)
+    }
+
+    /// Build the header value.
+    ///
+    /// This borrows the builder, so it can end a chain of `push` calls, and the builder
+    /// can continue to be used afterward.
+    pub fn build(&self) -> String {
+        self.header.clone()
+    }
+
+    fn push_token(&mut self, token: &str) {
+        if !self.header.is_empty() {
+            self.header.push_str(", ");
+        }
+
+        self.header.push_str(token);
+    }
+}
+
+/// Decoder for a single content encoding layer, registered with a
+/// [`DecoderRegistry`](struct.DecoderRegistry.html).
+///
+/// This crate doesn't decode anything itself, so implementations wrap actual codecs, such
+/// as those from `flate2`. Codecs have differing error types, so failures are reported as
+/// messages. Closures taking the encoded bytes implement this trait.
+#[cfg(feature = "alloc")]
+pub trait Decoder {
+    /// Decode the given bytes of one layer.
+    fn decode(&self, input: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+#[cfg(feature = "alloc")]
+impl<F: Fn(&[u8]) -> Result<Vec<u8>, String>> Decoder for F {
+    fn decode(&self, input: &[u8]) -> Result<Vec<u8>, String> {
+        self(input)
+    }
+}
+
+/// Set of decoders keyed by content encoding, which decodes every layer of a body.
+///
+/// Nonstandard encodings are matched ignoring ASCII case. Identity layers are passed
+/// through unchanged unless a decoder is registered for them.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: Vec<(ContentEncodingOwned, Box<dyn Decoder>)>,
+}
+
+#[cfg(feature = "alloc")]
+impl DecoderRegistry {
+    /// Create a new registry with no decoders.
+    pub fn new() -> Self {
+        DecoderRegistry::default()
+    }
+
+    /// Register a decoder for the given encoding, replacing any already registered.
+    pub fn register<D: Decoder + 'static>(&mut self, enc: ContentEncoding, decoder: D)
+        -> &mut Self
+    {
+        let decoder = Box::new(decoder);
+
+        match self.position(enc) {
+            Some(i) => self.decoders[i].1 = decoder,
+            None => self.decoders.push((enc.canonicalize().to_owned(), decoder)),
+        }
+
+        self
+    }
+
+    /// Retrieve the decoder registered for the given encoding, if any.
+    pub fn decoder(&self, enc: ContentEncoding) -> Option<&dyn Decoder> {
+        self.position(enc).map(|i| &*self.decoders[i].1)
+    }
+
+    fn position(&self, enc: ContentEncoding) -> Option<usize> {
+        let enc = enc.canonicalize();
+        self.decoders.iter().position(|d| same_coding(&d.0.as_borrowed(), &enc))
+    }
+
+    /// Decode the given body through every layer of the given `Content-Encoding` string,
+    /// in decode order, outermost first.
+    ///
+    /// This fails at the first layer with no registered decoder, other than identity, or
+    /// whose decoder fails.
+    pub fn decode_layers(&self, header: &str, input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut data = input.to_vec();
+
+        for enc in content_encodings(header) {
+            data = match self.decoder(enc) {
+                Some(decoder) => decoder.decode(&data)
+                    .map_err(|msg| DecodeError::Failed(enc.to_owned(), msg))?,
+                None if enc.is_identity() => data,
+                None => return Err(DecodeError::Unsupported(enc.to_owned())),
+            };
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Debug for DecoderRegistry {
+    /// Write the encodings with registered decoders.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_set().entries(self.decoders.iter().map(|d| &d.0)).finish()
+    }
+}
+
+/// HTTP content encoding scheme.
+///
+/// Strings are parsed either leniently, with [`new`](#method.new), which accepts any name
+/// and never fails, or strictly, with `TryFrom` or [`new_strict`](#method.new_strict),
+/// which reject names that aren't valid tokens. There's no `From<&str>` conversion, since
+/// core's blanket `TryFrom` implementation for `From` types would replace the strict one,
+/// so generic code expecting an infallible conversion can be passed `ContentEncoding::new`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum ContentEncoding<'a> {
+    /// Standard defined scheme.
+    Std(StdContentEncoding),
+    /// Unknown/nonstandard scheme with the contained name.
+    ///
+    /// This is guaranteed to have no surrounding whitespace and retains its original
+    /// casing, so it requires case-insensitive comparison to other strings, as done by the
+    /// `PartialEq<str>` implementation.
+    Other(&'a str),
+}
+
+impl<'a> ContentEncoding<'a> {
+    /// Parse a new `ContentEncoding` from the given string.
+    pub fn new(s: &'a str) -> Self {
+        ContentEncoding::from_raw(s, RawContentEncoding::new(s.as_bytes()))
+    }
+
+    /// Convert an encoding parsed from the bytes of the given string, borrowing any
+    /// nonstandard name from the string.
+    fn from_raw(s: &'a str, enc: RawContentEncoding<'a>) -> Self {
+        match enc {
+            RawContentEncoding::Std(enc) => ContentEncoding::Std(enc),
+            RawContentEncoding::Other(name) => {
+                // The name is a subslice of the string, trimmed of ASCII whitespace, so its
+                // bounds fall on character boundaries.
+                let start = name.as_ptr() as usize - s.as_ptr() as usize;
+                ContentEncoding::Other(&s[start..start + name.len()])
+            },
+        }
+    }
+
+    /// Parse a new `ContentEncoding` in a const context from the given lowercase token.
+    ///
+    /// The token must already be lowercase and have no surrounding whitespace. Tokens that
+    /// don't meet this precondition aren't recognized as standard encodings and are
+    /// instead stored as nonstandard names.
+    pub const fn new_ascii_lower(s: &'static str) -> ContentEncoding<'static> {
+        use self::StdContentEncoding::*;
+
+        let enc = match s.as_bytes() {
+            b"" => Some(Identity),
+            b"x-compress" => Some(Compress),
+            b"x-gzip" => Some(Gzip),
+            _ => StdContentEncoding::from_canonical(s),
+        };
+
+        match enc {
+            Some(enc) => ContentEncoding::Std(enc),
+            None => ContentEncoding::Other(s),
+        }
+    }
+
+    /// Parse a new `ContentEncoding` from the given string, rejecting nonstandard names
+    /// that aren't valid [tokens](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+    ///
+    /// Like [`new`](#method.new), surrounding whitespace is ignored and an empty string
+    /// parses as identity. Names longer than
+    /// [`DEFAULT_MAX_TOKEN_LEN`](constant.DEFAULT_MAX_TOKEN_LEN.html) bytes are also
+    /// rejected.
+    pub fn new_strict(s: &'a str) -> Result<Self, InvalidTokenError<'a>> {
+        ContentEncoding::new_strict_max(s, DEFAULT_MAX_TOKEN_LEN)
+    }
+
+    /// Parse a new `ContentEncoding` from the given string, like
+    /// [`new_strict`](#method.new_strict), rejecting names longer than the given number of
+    /// bytes.
+    pub fn new_strict_max(s: &'a str, max_len: usize) -> Result<Self, InvalidTokenError<'a>> {
+        let s = trim_ows(s);
+
+        // The length is checked first, so an overlong name isn't scanned.
+        if s.len() > max_len {
+            Err(InvalidTokenError { token: s, kind: ParseEncodingErrorKind::TooLong })
+        } else if s.is_empty() || is_token(s) {
+            Ok(ContentEncoding::new(s))
+        } else {
+            Err(InvalidTokenError { token: s, kind: ParseEncodingErrorKind::InvalidToken })
+        }
+    }
+
+    /// Retrieve the canonical name of the encoding.
+    ///
+    /// For a standard scheme this is its lowercase token, which has a `'static` lifetime.
+    /// For a nonstandard scheme this is the stored name, borrowed from the parsed string.
+    pub fn canonical_name(&self) -> &'a str {
+        match *self {
+            ContentEncoding::Std(enc) => enc.token(),
+            ContentEncoding::Other(s) => s,
+        }
+    }
+
+    /// Retrieve the length in bytes of the canonical name of the encoding.
+    ///
+    /// For a nonstandard scheme this is the length of the trimmed token as written, which
+    /// matches its range from
+    /// [`content_encodings_spanned`](fn.content_encodings_spanned.html). A standard scheme
+    /// parsed from an alias, such as `x-gzip`, has the length of its canonical token.
+    pub fn token_len(&self) -> usize {
+        self.canonical_name().len()
+    }
+
+    /// Compare the canonical name of the encoding to the given string, ignoring ASCII case
+    /// and any legacy `x-` prefix on either side.
+    ///
+    /// This is a pragmatic compatibility helper for peers that add or drop the `x-` prefix
+    /// on nonstandard names, as was once common, so `Other("x-foo")` equals `"foo"` and
+    /// `Std(Gzip)` equals `"x-gzip"`. Exact comparison is done by `PartialEq<str>`.
+    pub fn eq_ignoring_x_prefix(&self, other: &str) -> bool {
+        let other = strip_x_prefix(trim_ows(other));
+        strip_x_prefix(self.canonical_name()).eq_ignore_ascii_case(other)
+    }
+
+    /// Check if the encoding is the standard identity (no-op) scheme.
+    pub fn is_identity(&self) -> bool {
+        *self == ContentEncoding::Std(StdContentEncoding::Identity)
+    }
+
+    /// Check if the encoding is a standard scheme.
+    pub fn is_std(&self) -> bool {
+        self.as_std().is_some()
+    }
+
+    /// Retrieve the name of a nonstandard scheme exactly as it appeared in the parsed
+    /// string, with its original casing.
+    ///
+    /// Comparisons against strings with `==` ignore this casing.
+    pub fn other_original(&self) -> Option<&'a str> {
+        match *self {
+            ContentEncoding::Std(_) => None,
+            ContentEncoding::Other(s) => Some(s),
+        }
+    }
+
+    /// Retrieve the standard scheme, if the encoding is one.
+    ///
+    /// This borrows the encoding, for use with `filter_map` over references, such as
+    /// `encs.iter().filter_map(ContentEncoding::as_std)`.
+    ///
+    /// The result holds no lifetime, so it can be stored in place of the encoding, such as in
+    /// packed or long-lived structs, with `None` marking a nonstandard encoding whose name
+    /// must be kept separately.
+    pub fn as_std(&self) -> Option<StdContentEncoding> {
+        match *self {
+            ContentEncoding::Std(enc) => Some(enc),
+            ContentEncoding::Other(_) => None,
+        }
+    }
+
+    /// Convert the encoding into its standard scheme, if it's one.
+    ///
+    /// This takes the encoding by value, for use with `filter_map` over the parsing
+    /// iterators, such as `content_encodings(s).filter_map(ContentEncoding::into_std)`.
+    pub fn into_std(self) -> Option<StdContentEncoding> {
+        self.as_std()
+    }
+
+    /// Normalize the encoding so that semantically equal encodings compare and hash
+    /// equally.
+    ///
+    /// Parsing already produces normalized values, but an `Other` constructed directly may
+    /// hold the name of a standard encoding, such as `Other("GZIP")`, or surrounding
+    /// whitespace. Such names are reparsed into their standard scheme. Other nonstandard
+    /// names keep their original casing, so they still compare and hash by exact name.
+    pub fn canonicalize(self) -> Self {
+        match self {
+            ContentEncoding::Std(_) => self,
+            ContentEncoding::Other(s) => ContentEncoding::new(s),
+        }
+    }
+
+    /// Copy the encoding into an owned value that doesn't borrow from the parsed string.
+    #[cfg(feature = "alloc")]
+    pub fn to_owned(&self) -> ContentEncodingOwned {
+        match *self {
+            ContentEncoding::Std(enc) => ContentEncodingOwned::Std(enc),
+            ContentEncoding::Other(s) => ContentEncodingOwned::Other(String::from(s)),
+        }
+    }
+}
+
+impl<'a> core::fmt::Display for ContentEncoding<'a> {
+    /// Write the canonical token of a standard scheme, or the nonstandard name exactly as
+    /// stored.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            ContentEncoding::Std(enc) => enc.fmt(f),
+            ContentEncoding::Other(s) => f.write_str(s),
+        }
+    }
+}
+
+impl<'a> Default for ContentEncoding<'a> {
+    /// Create the standard identity encoding.
+    fn default() -> Self {
+        ContentEncoding::Std(StdContentEncoding::Identity)
+    }
+}
+
+impl<'a> From<StdContentEncoding> for ContentEncoding<'a> {
+    fn from(enc: StdContentEncoding) -> Self {
+        ContentEncoding::Std(enc)
+    }
+}
+
+impl<'a> core::convert::TryFrom<&'a str> for ContentEncoding<'a> {
+    type Error = InvalidTokenError<'a>;
+
+    /// Parse a `ContentEncoding` with [`new_strict`](#method.new_strict).
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        ContentEncoding::new_strict(s)
+    }
+}
+
+impl<'a> PartialEq<str> for ContentEncoding<'a> {
+    /// Compare the canonical name of the encoding to the given string, ignoring ASCII
+    /// case.
+    fn eq(&self, other: &str) -> bool {
+        self.canonical_name().eq_ignore_ascii_case(other)
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for ContentEncoding<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> PartialEq<StdContentEncoding> for ContentEncoding<'a> {
+    /// Compare to a standard scheme, which a nonstandard scheme never equals.
+    fn eq(&self, other: &StdContentEncoding) -> bool {
+        self.as_std() == Some(*other)
+    }
+}
+
+impl<'a> PartialEq<ContentEncoding<'a>> for StdContentEncoding {
+    fn eq(&self, other: &ContentEncoding<'a>) -> bool {
+        *other == *self
+    }
+}
+
+/// HTTP content encoding scheme that owns its nonstandard name, created by
+/// [`ContentEncoding::to_owned`](enum.ContentEncoding.html#method.to_owned).
+///
+/// This can be stored after the parsed header is dropped.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum ContentEncodingOwned {
+    /// Standard defined scheme.
+    Std(StdContentEncoding),
+    /// Unknown/nonstandard scheme with the contained name, with its original casing.
+    Other(String),
+}
+
+#[cfg(feature = "alloc")]
+impl ContentEncodingOwned {
+    /// Borrow the encoding as a `ContentEncoding`.
+    pub fn as_borrowed(&self) -> ContentEncoding<'_> {
+        match *self {
+            ContentEncodingOwned::Std(enc) => ContentEncoding::Std(enc),
+            ContentEncodingOwned::Other(ref s) => ContentEncoding::Other(s),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<ContentEncoding<'a>> for ContentEncodingOwned {
+    fn from(enc: ContentEncoding<'a>) -> Self {
+        enc.to_owned()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for ContentEncodingOwned {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        self.as_borrowed().fmt(f)
+    }
+}
+
+/// Content coding that may be an application-specific extension, created by
+/// [`parse_with_extensions`](fn.parse_with_extensions.html).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum ExtendedEncoding<'a> {
+    /// Standard defined scheme.
+    Std(StdContentEncoding),
+    /// Registered extension with the contained caller-defined identifier.
+    Extension(u16),
+    /// Unknown/nonstandard scheme with the contained name, which isn't registered.
+    Other(&'a str),
+}
+
+/// Element of a `Content-Encoding` header, created by
+/// [`content_encoding_segments`](fn.content_encoding_segments.html).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum Segment<'a> {
+    /// Standard defined scheme.
+    Known(StdContentEncoding),
+    /// Nonstandard scheme with a name that's a valid
+    /// [token](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+    Unknown(&'a str),
+    /// Malformed element that isn't a valid token, with surrounding whitespace removed.
+    Invalid(&'a str),
+}
+
+impl<'a> Segment<'a> {
+    /// Classify the given list element.
+    pub fn new(s: &'a str) -> Self {
+        match ContentEncoding::new_strict(s) {
+            Ok(ContentEncoding::Std(enc)) => Segment::Known(enc),
+            Ok(ContentEncoding::Other(name)) => Segment::Unknown(name),
+            Err(err) => Segment::Invalid(err.token()),
+        }
+    }
+}
+
+/// HTTP content encoding scheme parsed from raw header bytes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum RawContentEncoding<'a> {
+    /// Standard defined scheme.
+    Std(StdContentEncoding),
+    /// Unknown/nonstandard scheme with the contained name.
+    ///
+    /// This is guaranteed to have no surrounding whitespace and requires
+    /// case-insensitive comparison to other strings. It may not be valid UTF-8.
+    Other(&'a [u8]),
+}
+
+impl<'a> RawContentEncoding<'a> {
+    /// Parse a new `RawContentEncoding` from the given bytes.
+    ///
+    /// This is the primitive behind every parser, including
+    /// [`ContentEncoding::new`](enum.ContentEncoding.html#method.new).
+    pub fn new(s: &'a [u8]) -> Self {
+        let s = trim_ows_bytes(s);
+
+        match StdContentEncoding::from_bytes(s) {
+            Some(enc) => RawContentEncoding::Std(enc),
+            None => RawContentEncoding::Other(s),
+        }
+    }
+
+    /// Convert the encoding to a `ContentEncoding`, failing if a nonstandard name isn't
+    /// valid UTF-8.
+    pub fn to_utf8(&self) -> Result<ContentEncoding<'a>, core::str::Utf8Error> {
+        match *self {
+            RawContentEncoding::Std(enc) => Ok(ContentEncoding::Std(enc)),
+            RawContentEncoding::Other(name) =>
+                core::str::from_utf8(name).map(ContentEncoding::Other),
+        }
+    }
+}
+
+/// HTTP transfer coding scheme.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum TransferEncoding<'a> {
+    /// Chunked transfer coding, which has no content coding equivalent.
+    Chunked,
+    /// Coding shared with content encodings.
+    Content(ContentEncoding<'a>),
+}
+
+impl<'a> TransferEncoding<'a> {
+    /// Parse a new `TransferEncoding` from the given string.
+    pub fn new(s: &'a str) -> Self {
+        let s = trim_ows(s);
+
+        if s.eq_ignore_ascii_case("chunked") {
+            TransferEncoding::Chunked
+        } else {
+            TransferEncoding::Content(ContentEncoding::new(s))
+        }
+    }
+
+    /// Check if the coding is `chunked`.
+    pub fn is_chunked(&self) -> bool {
+        *self == TransferEncoding::Chunked
+    }
+}
+
+/// Canonical token of every standard scheme, paired with its scheme, in variant
+/// declaration order.
+///
+/// This is the table behind
+/// [`StdContentEncoding::token`](enum.StdContentEncoding.html#method.token), and so the
+/// `Display` implementation, and every token parses back to its scheme with `FromStr`.
+/// Legacy aliases such as `x-gzip` aren't listed.
+pub const ENCODING_TOKENS: &[(&str, StdContentEncoding)] = &[
+    ("aes128gcm", StdContentEncoding::Aes128Gcm),
+    ("br", StdContentEncoding::Brotli),
+    ("compress", StdContentEncoding::Compress),
+    ("dcb", StdContentEncoding::Dcb),
+    ("dcz", StdContentEncoding::Dcz),
+    ("deflate", StdContentEncoding::Deflate),
+    ("exi", StdContentEncoding::EfficientXML),
+    ("gzip", StdContentEncoding::Gzip),
+    ("identity", StdContentEncoding::Identity),
+    ("pack200-gzip", StdContentEncoding::Pack200Gzip),
+    ("zstd", StdContentEncoding::Zstd),
+];
+
+/// Standard content encoding scheme, as defined by
+/// [IANA](http://www.iana.org/assignments/http-parameters/http-parameters.xhtml#content-coding).
+///
+/// The legacy `x-compress` and `x-gzip` aliases parse as `Compress` and `Gzip`,
+/// respectively.
+///
+/// Schemes are ordered by variant declaration order, which carries no semantic meaning
+/// but allows use in sorted collections.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum StdContentEncoding {
+    /// AES-GCM encrypted content, as used by Web Push.
+    Aes128Gcm,
+    /// Brotli compressed data format.
+    Brotli,
+    /// Unix "compress" data format.
+    Compress,
+    /// Dictionary-compressed Brotli data format.
+    Dcb,
+    /// Dictionary-compressed Zstandard data format.
+    Dcz,
+    /// Deflate compressed data format.
+    Deflate,
+    /// W3C Efficient XML Interchange.
+    EfficientXML,
+    /// Gzip compressed data format.
+    Gzip,
+    /// No encoding.
+    Identity,
+    /// Java archive network transfer format.
+    Pack200Gzip,
+    /// Zstandard compressed data format.
+    Zstd,
+}
+
+impl StdContentEncoding {
+    /// Every standard scheme, in variant declaration order.
+    pub const ALL: &'static [StdContentEncoding] = &[
+        StdContentEncoding::Aes128Gcm,
+        StdContentEncoding::Brotli,
+        StdContentEncoding::Compress,
+        StdContentEncoding::Dcb,
+        StdContentEncoding::Dcz,
+        StdContentEncoding::Deflate,
+        StdContentEncoding::EfficientXML,
+        StdContentEncoding::Gzip,
+        StdContentEncoding::Identity,
+        StdContentEncoding::Pack200Gzip,
+        StdContentEncoding::Zstd,
+    ];
+
+    /// Misspelled alias of [`Brotli`](#variant.Brotli), kept for compatibility.
+    ///
+    /// This was previously a variant. As an associated constant, it must be named by its
+    /// full path, which also works in patterns, since glob imports such as
+    /// `use StdContentEncoding::*` don't bring it into scope.
+    #[deprecated(note = "use `StdContentEncoding::Brotli` instead")]
+    #[allow(non_upper_case_globals)]
+    pub const Brottli: StdContentEncoding = StdContentEncoding::Brotli;
+
+    /// Parse a standard scheme from the given ASCII token bytes.
+    ///
+    /// This behaves like the `FromStr` implementation, returning `None` for an unknown
+    /// token.
+    pub fn from_bytes(s: &[u8]) -> Option<Self> {
+        use self::StdContentEncoding::*;
+
+        let eq = |token: &[u8]| eq_lowercase(s, token);
+
+        // Dispatch on length first, so at most a few tokens are compared, and compare
+        // against lowercase tokens, so only the input needs case folding.
+        match s.len() {
+            // Assume empty means identity [RFC7231§5.3.4].
+            0 => Some(Identity),
+            2 if eq(b"br") => Some(Brotli),
+            3 if eq(b"dcb") => Some(Dcb),
+            3 if eq(b"dcz") => Some(Dcz),
+            3 if eq(b"exi") => Some(EfficientXML),
+            4 if eq(b"gzip") => Some(Gzip),
+            4 if eq(b"zstd") => Some(Zstd),
+            // Legacy alias [RFC7230§4.2.3].
+            6 if eq(b"x-gzip") => Some(Gzip),
+            7 if eq(b"deflate") => Some(Deflate),
+            8 if eq(b"compress") => Some(Compress),
+            8 if eq(b"identity") => Some(Identity),
+            9 if eq(b"aes128gcm") => Some(Aes128Gcm),
+            // Legacy alias [RFC7230§4.2.1].
+            10 if eq(b"x-compress") => Some(Compress),
+            12 if eq(b"pack200-gzip") => Some(Pack200Gzip),
+            _ => None,
+        }
+    }
+
+    /// Check if the scheme is commonly supported by decompression libraries.
+    ///
+    /// This is a heuristic, not a guarantee: it's true for `br`, `deflate`, `gzip`,
+    /// `identity`, and `zstd`, and false for more exotic schemes.
+    pub fn is_commonly_supported(&self) -> bool {
+        use self::StdContentEncoding::*;
+
+        match *self {
+            Brotli | Deflate | Gzip | Identity | Zstd => true,
+            Aes128Gcm | Compress | Dcb | Dcz | EfficientXML | Pack200Gzip => false,
+        }
+    }
+
+    /// Classify roughly how aggressively the scheme compresses.
+    ///
+    /// This is a heuristic for tuning buffers, not a measurement: actual ratios depend on
+    /// the content and compression level.
+    pub fn compression_class(&self) -> CompressionClass {
+        use self::StdContentEncoding::*;
+
+        match *self {
+            // Encryption doesn't shrink the content.
+            Aes128Gcm | Identity => CompressionClass::None,
+            Compress | Deflate | Gzip => CompressionClass::Light,
+            Brotli | Dcb | Dcz | EfficientXML | Pack200Gzip | Zstd => CompressionClass::Strong,
+        }
+    }
+
+    /// Check if the scheme is `deflate`.
+    pub fn is_deflate(&self) -> bool {
+        *self == StdContentEncoding::Deflate
+    }
+
+    /// Retrieve guidance on the raw-versus-zlib ambiguity of `deflate`, or `None` for any
+    /// other scheme.
+    ///
+    /// `deflate` is defined as a zlib-wrapped stream [RFC7230§4.2.2], but some servers
+    /// send raw deflate data instead, so downstream code can use this to warn or to decode
+    /// defensively.
+    pub fn deflate_ambiguity_note(&self) -> Option<&'static str> {
+        if self.is_deflate() {
+            Some("deflate should be a zlib stream (RFC 1950), but some servers send raw \
+                  deflate data (RFC 1951); check for a zlib header before decoding")
+        } else {
+            None
+        }
+    }
+
+    /// Retrieve the well-known signature that starts a stream in the scheme, if any.
+    ///
+    /// This can be used to sniff or verify an encoded body. Schemes without a fixed
+    /// signature, such as `br`, `deflate`, `exi`, and `identity`, return `None`.
+    pub fn magic_bytes(&self) -> Option<&'static [u8]> {
+        use self::StdContentEncoding::*;
+
+        match *self {
+            Compress => Some(&[0x1f, 0x9d]),
+            Dcb => Some(&[0xff, 0x44, 0x43, 0x42]),
+            Dcz => Some(&[0x5e, 0x2a, 0x4d, 0x18, 0x20, 0x00, 0x00, 0x00]),
+            // Pack200 archives are wrapped in gzip for transfer.
+            Gzip | Pack200Gzip => Some(&[0x1f, 0x8b]),
+            Zstd => Some(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Aes128Gcm | Brotli | Deflate | EfficientXML | Identity => None,
+        }
+    }
+
+    /// Parse a standard scheme from its exact canonical token, as returned by
+    /// [`token`](#method.token).
+    ///
+    /// This is a fast path for already-normalized tokens: comparison is case-sensitive, and
+    /// anything else, including aliases, mixed case, and the empty string, returns `None`.
+    pub const fn from_canonical(s: &str) -> Option<Self> {
+        use self::StdContentEncoding::*;
+
+        match s.as_bytes() {
+            b"aes128gcm" => Some(Aes128Gcm),
+            b"br" => Some(Brotli),
+            b"compress" => Some(Compress),
+            b"dcb" => Some(Dcb),
+            b"dcz" => Some(Dcz),
+            b"deflate" => Some(Deflate),
+            b"exi" => Some(EfficientXML),
+            b"gzip" => Some(Gzip),
+            b"identity" => Some(Identity),
+            b"pack200-gzip" => Some(Pack200Gzip),
+            b"zstd" => Some(Zstd),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the canonical token for the scheme.
+    ///
+    /// This is the same string written by the `Display` implementation, but is usable in
+    /// const contexts.
+    pub const fn token(self) -> &'static str {
+        // The table is in declaration order, so it's indexed by discriminant.
+        ENCODING_TOKENS[self as usize].0
+    }
+}
+
+/// Rough class of how aggressively a content encoding compresses, returned by
+/// [`compression_class`](enum.StdContentEncoding.html#method.compression_class).
+///
+/// Classes are ordered from least to most compression.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub enum CompressionClass {
+    /// No compression, such as `identity` and `aes128gcm`.
+    None,
+    /// General-purpose compression, such as `gzip` and `deflate`.
+    Light,
+    /// Modern or specialized compression, such as `br` and `zstd`.
+    Strong,
+}
+
+impl Default for StdContentEncoding {
+    /// Create the identity encoding.
+    fn default() -> Self {
+        StdContentEncoding::Identity
+    }
+}
+
+impl core::str::FromStr for StdContentEncoding {
+    type Err = ParseEncodingError;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        StdContentEncoding::from_bytes(s.as_bytes())
+            .ok_or_else(|| ParseEncodingError::new(ParseEncodingErrorKind::Unknown, s))
+    }
+}
+
+impl core::fmt::Display for StdContentEncoding {
+    /// Write the canonical token for the scheme.
+    ///
+    /// Legacy aliases such as `x-gzip` aren't retained by parsing, so they're written in
+    /// their canonical form (`gzip`).
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.token())
+    }
+}
+
+impl AsRef<str> for StdContentEncoding {
+    /// Borrow the canonical token for the scheme.
+    fn as_ref(&self) -> &str {
+        self.token()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StdContentEncoding {
+    /// Serialize as the canonical token.
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.token())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StdContentEncoding {
+    /// Deserialize from a token string, failing if it isn't a standard encoding.
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = StdContentEncoding;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("a standard content encoding token")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(|_| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+        }
+
+        d.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for ContentEncoding<'a> {
+    /// Serialize as the canonical token of a standard scheme or the stored nonstandard
+    /// name.
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            ContentEncoding::Std(enc) => enc.serialize(s),
+            ContentEncoding::Other(name) => s.serialize_str(name),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> serde::Deserialize<'de> for ContentEncoding<'a> {
+    /// Deserialize from a token string, with unknown encodings becoming `Other`.
+    ///
+    /// Since `Other` borrows its name, the string must be borrowed directly from the
+    /// deserializer input. Strings that must be unescaped or copied by the deserializer,
+    /// such as JSON strings containing escape sequences, are rejected.
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ContentEncoding<'de>;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("a borrowed content encoding token")
+            }
+
+            fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str)
+                -> Result<Self::Value, E>
+            {
+                Ok(ContentEncoding::new(v))
+            }
+        }
+
+        d.deserialize_str(Visitor)
+    }
+}
+
+/// Error returned when a token can't be parsed as a content encoding.
+///
+/// This is the error for the `FromStr` implementation, which can't borrow from its input,
+/// so the token is copied when the `alloc` feature is enabled. Fallible parsers that
+/// borrow their input return [`InvalidTokenError`](struct.InvalidTokenError.html)
+/// instead, which converts into this type.
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub struct ParseEncodingError {
+    kind: ParseEncodingErrorKind,
+    #[cfg(feature = "alloc")]
+    token: String,
+}
+
+impl ParseEncodingError {
+    fn new(kind: ParseEncodingErrorKind, token: &str) -> Self {
+        #[cfg(not(feature = "alloc"))]
+        let _ = token;
+
+        ParseEncodingError {
+            kind,
+            #[cfg(feature = "alloc")]
+            token: String::from(token),
+        }
+    }
+
+    /// Retrieve the reason the token failed to parse.
+    pub fn kind(&self) -> ParseEncodingErrorKind {
+        self.kind
+    }
+
+    /// Retrieve the token that failed to parse.
+    #[cfg(feature = "alloc")]
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl core::fmt::Display for ParseEncodingError {
+    #[cfg(feature = "alloc")]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} {:?}", self.kind.description(), self.token)
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.kind.description())
+    }
+}
+
+/// Reason a token failed to parse.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum ParseEncodingErrorKind {
+    /// Token isn't a standard content encoding.
+    Unknown,
+    /// Token contains characters not allowed by the [token
+    /// grammar](https://tools.ietf.org/html/rfc7230#section-3.2.6).
+    InvalidToken,
+    /// Token is longer than the maximum allowed by a strict parser.
+    TooLong,
+}
+
+impl ParseEncodingErrorKind {
+    fn description(&self) -> &'static str {
+        match *self {
+            ParseEncodingErrorKind::Unknown => "unknown content encoding",
+            ParseEncodingErrorKind::InvalidToken => "invalid content encoding token",
+            ParseEncodingErrorKind::TooLong => "content encoding token too long",
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseEncodingError {}
+
+impl<'a> From<InvalidTokenError<'a>> for ParseEncodingError {
+    fn from(err: InvalidTokenError<'a>) -> Self {
+        ParseEncodingError::new(err.kind, err.token)
+    }
+}
+
+/// Error returned when a string isn't a valid content encoding
+/// [token](https://tools.ietf.org/html/rfc7230#section-3.2.6), or is too long.
+///
+/// Unlike [`ParseEncodingError`](struct.ParseEncodingError.html), this borrows the
+/// offending token, so it's available without allocating.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct InvalidTokenError<'a> {
+    token: &'a str,
+    kind: ParseEncodingErrorKind,
+}
+
+impl<'a> InvalidTokenError<'a> {
+    /// Retrieve the token that failed to parse, with surrounding whitespace removed.
+    pub fn token(&self) -> &'a str {
+        self.token
+    }
+
+    /// Retrieve the reason the token failed to parse, which is either
+    /// [`InvalidToken`](enum.ParseEncodingErrorKind.html#variant.InvalidToken) or
+    /// [`TooLong`](enum.ParseEncodingErrorKind.html#variant.TooLong).
+    pub fn kind(&self) -> ParseEncodingErrorKind {
+        self.kind
+    }
+}
+
+impl<'a> core::fmt::Display for InvalidTokenError<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.kind {
+            // An overlong token could be huge, so only its length is written.
+            ParseEncodingErrorKind::TooLong =>
+                write!(f, "{} ({} bytes)", self.kind.description(), self.token.len()),
+            _ => write!(f, "{} {:?}", self.kind.description(), self.token),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> std::error::Error for InvalidTokenError<'a> {}
+
+/// Error returned when an `Accept-Encoding` weight doesn't follow the [qvalue
+/// grammar](https://tools.ietf.org/html/rfc7231#section-5.3.1).
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct InvalidQvalueError<'a> {
+    value: &'a str,
+}
+
+impl<'a> InvalidQvalueError<'a> {
+    /// Retrieve the weight that failed to parse, with surrounding whitespace removed.
+    pub fn value(&self) -> &'a str {
+        self.value
+    }
+}
+
+impl<'a> core::fmt::Display for InvalidQvalueError<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "invalid quality value {:?}", self.value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> std::error::Error for InvalidQvalueError<'a> {}
+
+/// Error returned when a `Content-Encoding` header is semantically invalid.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum EncodingError<'a> {
+    /// `identity` is combined with other encodings, between two other layers.
+    IdentityCombined,
+    /// `identity` is combined with other encodings as the innermost layer, listed first.
+    IdentityInnermost,
+    /// `identity` is combined with other encodings as the outermost layer, listed last.
+    IdentityOutermost,
+    /// The contained encoding is applied more than once.
+    Duplicate(ContentEncoding<'a>),
+    /// The `*` wildcard, which is only valid in `Accept-Encoding`, is used as an encoding.
+    Wildcard,
+    /// The contained layer has parameters, such as a `q` weight, which are only valid in
+    /// `Accept-Encoding`.
+    Parameter(&'a str),
+    /// The contained layer isn't a valid token.
+    InvalidToken(InvalidTokenError<'a>),
+}
+
+impl<'a> core::fmt::Display for EncodingError<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            EncodingError::IdentityCombined =>
+                f.write_str("identity combined with other content encodings"),
+            EncodingError::IdentityInnermost =>
+                f.write_str("identity as the innermost of multiple content encodings"),
+            EncodingError::IdentityOutermost =>
+                f.write_str("identity as the outermost of multiple content encodings"),
+            EncodingError::Duplicate(enc) =>
+                write!(f, "duplicate content encoding \"{}\"", enc),
+            EncodingError::Wildcard =>
+                f.write_str("wildcard \"*\" used as a content encoding"),
+            EncodingError::Parameter(layer) =>
+                write!(f, "parameters used in content encoding \"{}\"", layer),
+            EncodingError::InvalidToken(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> std::error::Error for EncodingError<'a> {}
+
+/// Error returned when a body can't be decoded by a
+/// [`DecoderRegistry`](struct.DecoderRegistry.html).
+#[cfg(feature = "alloc")]
+#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+pub enum DecodeError {
+    /// No decoder is registered for the contained layer.
+    Unsupported(ContentEncodingOwned),
+    /// The decoder for the contained layer failed with the contained message.
+    Failed(ContentEncodingOwned, String),
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            DecodeError::Unsupported(ref enc) =>
+                write!(f, "no decoder for content encoding \"{}\"", enc),
+            DecodeError::Failed(ref enc, ref msg) =>
+                write!(f, "failed to decode content encoding \"{}\": {}", enc, msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// Error returned when a header declares more content encoding layers than allowed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct LayerLimitError {
+    layers: usize,
+    max: usize,
+}
+
+impl LayerLimitError {
+    /// Retrieve the number of layers declared by the header.
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// Retrieve the maximum number of layers allowed.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl core::fmt::Display for LayerLimitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} content encoding layers exceeds limit of {}", self.layers, self.max)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayerLimitError {}
+
+/// Run every parser over arbitrary input, checking invariants that must always hold.
+///
+/// This is the entry point for the `cargo fuzz` target in the `fuzz` directory, which can
+/// be run from the repository root with `cargo +nightly fuzz run parse -- -timeout=5`. The
+/// timeout reports any input that takes more than 5 seconds, which catches parsers that
+/// aren't linear in the input length.
+#[cfg(any(fuzzing, test))]
+#[doc(hidden)]
+pub fn fuzz_parse(data: &[u8]) {
+    let layers = data.iter().filter(|&&b| b == b',').count() + 1;
+
+    assert_eq!(content_encodings_bytes(data).count(), layers);
+    assert_eq!(content_encodings_bytes(data).rev().count(), layers);
+
+    for enc in content_encodings_bytes(data) {
+        if let RawContentEncoding::Other(s) = enc {
+            assert!(!s.is_empty());
+            assert_eq!(trim_ows_bytes(s), s);
+        }
+    }
+
+    let s = match core::str::from_utf8(data) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+
+    assert_eq!(content_encodings(s).len(), layers);
+    assert_eq!(content_encodings(s).count(), layers);
+    assert_eq!(content_encodings(s).rev().count(), layers);
+    assert_eq!(transfer_encodings(s).count(), layers);
+    assert_eq!(content_encodings_checked(s).count(), layers);
+
+    for ((enc, range), raw) in content_encodings_spanned(s).zip(content_encodings_bytes(data)) {
+        assert_eq!(ContentEncoding::new(&s[range]), enc);
+
+        match (enc, raw) {
+            (ContentEncoding::Std(a), RawContentEncoding::Std(b)) => assert_eq!(a, b),
+            (ContentEncoding::Other(a), RawContentEncoding::Other(b)) =>
+                assert_eq!(a.as_bytes(), b),
+            _ => panic!("str and bytes parsers disagree"),
+        }
+    }
+
+    assert!(content_encodings_lenient(s).count() <= layers);
+
+    for (_, q) in accept_encodings(s).chain(accept_encodings_recovering(s, 1.0)) {
+        assert!((0.0..=1.0).contains(&q));
+    }
+
+    assert_eq!(accept_encodings_strict(s).count(), accept_encodings(s).count());
+
+    let _ = validate_content_encoding(s);
+}
+
+/// Remove the legacy `x-` prefix, in any case, from the given name.
+fn strip_x_prefix(s: &str) -> &str {
+    match s.get(..2) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("x-") => &s[2..],
+        _ => s,
+    }
+}
+
+/// Check if the given bytes equal the given lowercase token, ignoring ASCII case
+/// [RFC7231§3.1.2.1].
+fn eq_lowercase(s: &[u8], token: &[u8]) -> bool {
+    s.len() == token.len() && s.iter().zip(token).all(|(&a, &b)| a.to_ascii_lowercase() == b)
+}
+
+/// Trim optional whitespace [RFC7230§3.2.3] from the given string, along with any CR and
+/// LF left over from obsolete line folding [RFC7230§3.2.4].
+fn trim_ows(s: &str) -> &str {
+    // Whitespace is ASCII, so the bounds always fall on character boundaries. Scanning
+    // bytes avoids decoding characters.
+    let (start, end) = ows_bounds(s.as_bytes());
+    &s[start..end]
+}
+
+/// Trim optional whitespace from the given bytes, like `trim_ows`.
+fn trim_ows_bytes(s: &[u8]) -> &[u8] {
+    let (start, end) = ows_bounds(s);
+    &s[start..end]
+}
+
+/// Find the range of the given bytes that remains after trimming optional whitespace.
+///
+/// Like `str::trim_matches`, this is empty at the start when the bytes are all whitespace.
+fn ows_bounds(s: &[u8]) -> (usize, usize) {
+    let start = match s.iter().position(|&b| !is_ows(b)) {
+        Some(start) => start,
+        None => return (0, 0),
+    };
+
+    // There's at least one non-whitespace byte.
+    let end = s.iter().rposition(|&b| !is_ows(b)).unwrap() + 1;
+
+    (start, end)
+}
+
+fn is_ows(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
+}
+
+/// Check if the given string is a valid token [RFC7230§3.2.6].
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_tchar)
+}
+
+/// Check if the given byte is a valid token character [RFC7230§3.2.6].
+fn is_tchar(b: u8) -> bool {
+    match b {
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_' |
+        b'`' | b'|' | b'~' => true,
+        _ => b.is_ascii_alphanumeric(),
+    }
+}
+
+/// C-compatible interface to the parser, enabled by the `ffi` feature.
+///
+/// A header is parsed with [`uhttp_content_encodings`](fn.uhttp_content_encodings.html),
+/// which reports each layer, outermost first, to a callback.
+///
+/// ## Memory ownership
+///
+/// The caller owns the header string, which must stay valid and unmodified for the
+/// duration of the call. Nothing is allocated or freed on either side: the names of
+/// nonstandard layers point into the header string itself, so they're only valid as long
+/// as it is, and aren't NUL-terminated. The layer passed to the callback is only valid
+/// during the callback.
+#[cfg(feature = "ffi")]
+pub mod ffi {
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int, c_void};
+    use std::panic::{self, AssertUnwindSafe};
+
+    use super::{content_encodings_bytes, RawContentEncoding, StdContentEncoding};
+
+    /// Value of `UhttpContentEncoding::kind` for a nonstandard encoding.
+    pub const UHTTP_CONTENT_ENCODING_OTHER: c_int = -1;
+    /// Value of `UhttpContentEncoding::kind` for `aes128gcm`.
+    pub const UHTTP_CONTENT_ENCODING_AES128GCM: c_int = 0;
+    /// Value of `UhttpContentEncoding::kind` for `br`.
+    pub const UHTTP_CONTENT_ENCODING_BR: c_int = 1;
+    /// Value of `UhttpContentEncoding::kind` for `compress` and `x-compress`.
+    pub const UHTTP_CONTENT_ENCODING_COMPRESS: c_int = 2;
+    /// Value of `UhttpContentEncoding::kind` for `dcb`.
+    pub const UHTTP_CONTENT_ENCODING_DCB: c_int = 3;
+    /// Value of `UhttpContentEncoding::kind` for `dcz`.
+    pub const UHTTP_CONTENT_ENCODING_DCZ: c_int = 4;
+    /// Value of `UhttpContentEncoding::kind` for `deflate`.
+    pub const UHTTP_CONTENT_ENCODING_DEFLATE: c_int = 5;
+    /// Value of `UhttpContentEncoding::kind` for `exi`.
+    pub const UHTTP_CONTENT_ENCODING_EXI: c_int = 6;
+    /// Value of `UhttpContentEncoding::kind` for `gzip` and `x-gzip`.
+    pub const UHTTP_CONTENT_ENCODING_GZIP: c_int = 7;
+    /// Value of `UhttpContentEncoding::kind` for `identity` and empty layers.
+    pub const UHTTP_CONTENT_ENCODING_IDENTITY: c_int = 8;
+    /// Value of `UhttpContentEncoding::kind` for `pack200-gzip`.
+    pub const UHTTP_CONTENT_ENCODING_PACK200_GZIP: c_int = 9;
+    /// Value of `UhttpContentEncoding::kind` for `zstd`.
+    pub const UHTTP_CONTENT_ENCODING_ZSTD: c_int = 10;
+
+    /// Error returned when the header or callback is null.
+    pub const UHTTP_CONTENT_ENCODING_ENULL: c_int = -1;
+    /// Error returned when parsing panicked.
+    pub const UHTTP_CONTENT_ENCODING_EPANIC: c_int = -2;
+
+    /// Content encoding layer reported to the callback.
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug)]
+    pub struct UhttpContentEncoding {
+        /// One of the `UHTTP_CONTENT_ENCODING_*` kind constants, such as
+        /// `UHTTP_CONTENT_ENCODING_GZIP`, or `UHTTP_CONTENT_ENCODING_OTHER` for a
+        /// nonstandard encoding.
+        ///
+        /// Kinds are fixed, so encodings added later get new values rather than renumbering
+        /// existing ones.
+        pub kind: c_int,
+        /// Name of a nonstandard encoding, pointing into the header, or null for a
+        /// standard encoding.
+        pub name: *const u8,
+        /// Length in bytes of the name, or 0 for a standard encoding.
+        pub len: usize,
+    }
+
+    /// Retrieve the fixed kind constant of the given standard encoding.
+    fn kind(enc: StdContentEncoding) -> c_int {
+        match enc {
+            StdContentEncoding::Aes128Gcm => UHTTP_CONTENT_ENCODING_AES128GCM,
+            StdContentEncoding::Brotli => UHTTP_CONTENT_ENCODING_BR,
+            StdContentEncoding::Compress => UHTTP_CONTENT_ENCODING_COMPRESS,
+            StdContentEncoding::Dcb => UHTTP_CONTENT_ENCODING_DCB,
+            StdContentEncoding::Dcz => UHTTP_CONTENT_ENCODING_DCZ,
+            StdContentEncoding::Deflate => UHTTP_CONTENT_ENCODING_DEFLATE,
+            StdContentEncoding::EfficientXML => UHTTP_CONTENT_ENCODING_EXI,
+            StdContentEncoding::Gzip => UHTTP_CONTENT_ENCODING_GZIP,
+            StdContentEncoding::Identity => UHTTP_CONTENT_ENCODING_IDENTITY,
+            StdContentEncoding::Pack200Gzip => UHTTP_CONTENT_ENCODING_PACK200_GZIP,
+            StdContentEncoding::Zstd => UHTTP_CONTENT_ENCODING_ZSTD,
+        }
+    }
+
+    /// Callback receiving each layer and the caller's data pointer, which returns 0 to
+    /// continue parsing or any other value to stop.
+    pub type UhttpContentEncodingCallback =
+        extern "C" fn(layer: *const UhttpContentEncoding, data: *mut c_void) -> c_int;
+
+    /// Parse the given NUL-terminated `Content-Encoding` header, passing each layer, in
+    /// the order they must be decoded, to the given callback along with `data`.
+    ///
+    /// The header doesn't need to be valid UTF-8. This returns the number of layers passed
+    /// to the callback, including the one it stopped at, or a negative error code. Panics
+    /// are caught rather than unwinding into the caller.
+    ///
+    /// # Safety
+    ///
+    /// `header` must be null or point to a NUL-terminated string that's valid for reads
+    /// for the duration of the call.
+    #[no_mangle]
+    pub unsafe extern "C" fn uhttp_content_encodings(
+        header: *const c_char,
+        callback: Option<UhttpContentEncodingCallback>,
+        data: *mut c_void,
+    ) -> c_int {
+        let callback = match callback {
+            Some(callback) if !header.is_null() => callback,
+            _ => return UHTTP_CONTENT_ENCODING_ENULL,
+        };
+
+        let header = CStr::from_ptr(header).to_bytes();
+
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut layers: c_int = 0;
+
+            for enc in content_encodings_bytes(header) {
+                let layer = match enc {
+                    RawContentEncoding::Std(enc) => UhttpContentEncoding {
+                        kind: kind(enc),
+                        name: std::ptr::null(),
+                        len: 0,
+                    },
+                    RawContentEncoding::Other(name) => UhttpContentEncoding {
+                        kind: UHTTP_CONTENT_ENCODING_OTHER,
+                        name: name.as_ptr(),
+                        len: name.len(),
+                    },
+                };
+
+                layers = layers.saturating_add(1);
+
+                if callback(&layer, data) != 0 {
+                    break;
+                }
+            }
+
+            layers
+        }));
+
+        res.unwrap_or(UHTTP_CONTENT_ENCODING_EPANIC)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ce() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(ContentEncoding::new("aes128gcm"), Std(Aes128Gcm));
+        assert_eq!(ContentEncoding::new(" AES128GCM\t"), Std(Aes128Gcm));
+        assert_eq!(ContentEncoding::new("Aes128Gcm"), Std(Aes128Gcm));
+        assert_eq!(ContentEncoding::new("aes256gcm"), Other("aes256gcm"));
+        assert_eq!(ContentEncoding::new("br"), Std(Brotli));
+        assert_eq!(ContentEncoding::new("\t\t\rBr  "), Std(Brotli));
+        assert_eq!(ContentEncoding::new("compress"), Std(Compress));
+        assert_eq!(ContentEncoding::new("  COMpress "), Std(Compress));
+        assert_eq!(ContentEncoding::new("dcb"), Std(Dcb));
+        assert_eq!(ContentEncoding::new(" DCB "), Std(Dcb));
+        assert_eq!(ContentEncoding::new("dcz"), Std(Dcz));
+        assert_eq!(ContentEncoding::new("\tdCz"), Std(Dcz));
+        assert_eq!(ContentEncoding::new("deflate"), Std(Deflate));
+        assert_eq!(ContentEncoding::new("\t\n dEFLAte "), Std(Deflate));
+        assert_eq!(ContentEncoding::new("exi"), Std(EfficientXML));
+        assert_eq!(ContentEncoding::new("\tEXI\t"), Std(EfficientXML));
+        assert_eq!(ContentEncoding::new("gzip"), Std(Gzip));
+        assert_eq!(ContentEncoding::new("  \tgZIP"), Std(Gzip));
+        assert_eq!(ContentEncoding::new("identity"), Std(Identity));
+        assert_eq!(ContentEncoding::new("\niDENtiTY\r\r\r "), Std(Identity));
+        assert_eq!(ContentEncoding::new(""), Std(Identity));
+        assert_eq!(ContentEncoding::new("    \t "), Std(Identity));
+        assert_eq!(ContentEncoding::new("pack200-gzip"), Std(Pack200Gzip));
+        assert_eq!(ContentEncoding::new("  PaCK200-GZip "), Std(Pack200Gzip));
+        assert_eq!(ContentEncoding::new("x-compress"), Std(Compress));
+        assert_eq!(ContentEncoding::new(" \tX-Compress\t"), Std(Compress));
+        assert_eq!(ContentEncoding::new("x-gzip"), Std(Gzip));
+        assert_eq!(ContentEncoding::new("  x-GZIP \r\n"), Std(Gzip));
+        assert_eq!(ContentEncoding::new("x-deflate"), Other("x-deflate"));
+        assert_eq!(ContentEncoding::new("zstd"), Std(Zstd));
+        assert_eq!(ContentEncoding::new(" ZStd\t"), Std(Zstd));
+        assert_eq!(ContentEncoding::new("ÆØБД❤"), Other("ÆØБД❤"));
+    }
+
+    #[test]
+    fn test_content_encodings_macro() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        const TABLE: [ContentEncoding<'static>; 3] =
+            content_encodings![Gzip, Brotli, Other("custom")];
+
+        assert_eq!(TABLE, [Std(Gzip), Std(Brotli), Other("custom")]);
+        assert_eq!(content_encodings![Zstd], [Std(Zstd)]);
+        assert_eq!(content_encodings![Other("a"), Identity,], [Other("a"), Std(Identity)]);
+
+        let empty: [ContentEncoding; 0] = content_encodings![];
+        assert_eq!(empty.len(), 0);
+
+        assert_eq!(&content_encodings![Gzip, Deflate][..],
+                   &parse_all("deflate, gzip")[..]);
+    }
+
+    #[test]
+    fn test_fuzz_pathological() {
+        // Linear scaling is measured by the `pathological` benchmarks and bounded by the fuzz
+        // target's timeout, rather than timed here.
+        fuzz_parse(b"");
+        fuzz_parse(&[b','; 100_000]);
+        fuzz_parse(&[0; 100_000]);
+        fuzz_parse(&[b'a'; 100_000]);
+        fuzz_parse(&[b'"'; 1000]);
+        fuzz_parse(&[b';'; 1000]);
+        fuzz_parse(&[0xff; 1000]);
+        fuzz_parse(b"gzip\x00, \x00br\x00,\x00");
+        fuzz_parse("ÆØБД❤, gzip;q=0.5, \"a,b\"".as_bytes());
+        fuzz_parse(&"gzip, ".repeat(10_000).into_bytes());
+        fuzz_parse(&";q=".repeat(10_000).into_bytes());
+
+        let distinct = (0..500).map(|i| format!("x{}", i)).collect::<Vec<_>>().join(", ");
+        assert_eq!(validate_content_encoding(&distinct), Ok(()));
+        fuzz_parse(distinct.as_bytes());
+
+        let mut token = vec![b'x'; 1_000_000];
+        token.extend_from_slice(b", gzip");
+        assert_eq!(content_encodings_bytes(&token).count(), 2);
+        fuzz_parse(&token);
+    }
+
+    #[test]
+    fn test_fuzz_random() {
+        const ALPHABET: &[u8] = b",;=\"\\ \t\r\n\x00\x7f\xc3\xa9\xffqQ0.1-*gzipbrx";
+
+        // Xorshift, to keep runs reproducible without extra dependencies.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let len = (next() % 48) as usize;
+            let data = (0..len)
+                .map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize])
+                .collect::<Vec<_>>();
+
+            fuzz_parse(&data);
+        }
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn test_ffi() {
+        use std::os::raw::{c_int, c_void};
+        use std::ptr;
+        use std::slice;
+        use super::ffi::*;
+
+        type Layers = Vec<(c_int, Vec<u8>)>;
+
+        extern "C" fn collect(layer: *const UhttpContentEncoding, data: *mut c_void) -> c_int {
+            let (layer, layers) = unsafe { (&*layer, &mut *(data as *mut Layers)) };
+
+            let name = if layer.name.is_null() {
+                vec![]
+            } else {
+                unsafe { slice::from_raw_parts(layer.name, layer.len) }.to_vec()
+            };
+
+            layers.push((layer.kind, name));
+
+            // Stop at the first nonstandard layer.
+            (layer.kind == UHTTP_CONTENT_ENCODING_OTHER) as c_int
+        }
+
+        let parse = |header: &[u8]| {
+            let mut layers = Layers::new();
+            let data = &mut layers as *mut Layers as *mut c_void;
+            let header = header.as_ptr() as *const _;
+            let n = unsafe { uhttp_content_encodings(header, Some(collect), data) };
+            (n, layers)
+        };
+
+        assert_eq!(parse(b"deflate, identity\0"), (2, vec![
+            (UHTTP_CONTENT_ENCODING_IDENTITY, vec![]),
+            (UHTTP_CONTENT_ENCODING_DEFLATE, vec![]),
+        ]));
+        assert_eq!(parse(b"gzip, X-Caf\xe9 , br\0"), (2, vec![
+            (UHTTP_CONTENT_ENCODING_BR, vec![]),
+            (UHTTP_CONTENT_ENCODING_OTHER, b"X-Caf\xe9".to_vec()),
+        ]));
+        assert_eq!(parse(b"\0"), (1, vec![(UHTTP_CONTENT_ENCODING_IDENTITY, vec![])]));
+
+        let header = b"aes128gcm, br, compress, dcb, dcz, deflate, exi, x-gzip, identity, \
+                       pack200-gzip, zstd\0";
+        assert_eq!(parse(header).1.iter().map(|l| l.0).collect::<Vec<_>>(), vec![
+            UHTTP_CONTENT_ENCODING_ZSTD,
+            UHTTP_CONTENT_ENCODING_PACK200_GZIP,
+            UHTTP_CONTENT_ENCODING_IDENTITY,
+            UHTTP_CONTENT_ENCODING_GZIP,
+            UHTTP_CONTENT_ENCODING_EXI,
+            UHTTP_CONTENT_ENCODING_DEFLATE,
+            UHTTP_CONTENT_ENCODING_DCZ,
+            UHTTP_CONTENT_ENCODING_DCB,
+            UHTTP_CONTENT_ENCODING_COMPRESS,
+            UHTTP_CONTENT_ENCODING_BR,
+            UHTTP_CONTENT_ENCODING_AES128GCM,
+        ]);
+        assert_eq!(UHTTP_CONTENT_ENCODING_GZIP, 7);
+        assert_eq!(UHTTP_CONTENT_ENCODING_ZSTD, 10);
+
+        let mut layers = Layers::new();
+        let data = &mut layers as *mut Layers as *mut c_void;
+
+        unsafe {
+            assert_eq!(uhttp_content_encodings(ptr::null(), Some(collect), data),
+                       UHTTP_CONTENT_ENCODING_ENULL);
+            assert_eq!(uhttp_content_encodings(b"gzip\0".as_ptr() as *const _, None, data),
+                       UHTTP_CONTENT_ENCODING_ENULL);
+        }
+
+        assert!(layers.is_empty());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing() {
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        type Recorded = Vec<(&'static str, String)>;
+
+        struct Fields(Recorded);
+
+        struct Capture(Arc<Mutex<Vec<Recorded>>>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push((field.name(), format!("{:?}", value)));
+            }
+        }
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _: &Metadata) -> bool { true }
+            fn new_span(&self, _: &Attributes) -> Id { Id::from_u64(1) }
+            fn record(&self, _: &Id, _: &Record) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        tracing::subscriber::with_default(Capture(events.clone()), || {
+            content_encodings("gzip, X-Custom, a b, br");
+            content_encodings("");
+        });
+
+        let headers = Arc::new(Mutex::new(Vec::new()));
+
+        // Each layer is counted by rescanning both headers, which mustn't trace again.
+        tracing::subscriber::with_default(Capture(headers.clone()), || {
+            assert!(same_coding_set("gzip, br, gzip, zstd", "zstd, gzip, br, gzip"));
+        });
+
+        let headers = headers.lock().unwrap();
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0][1], ("layers", "4".to_string()));
+        assert_eq!(headers[1][1], ("layers", "4".to_string()));
+
+        let events = events.lock().unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], vec![
+            ("message", "parsed Content-Encoding header".to_string()),
+            ("layers", "4".to_string()),
+            ("unknown", "X-Custom".to_string()),
+            ("invalid", "a b".to_string()),
+        ]);
+        assert_eq!(events[1], vec![
+            ("message", "parsed Content-Encoding header".to_string()),
+            ("layers", "1".to_string()),
+            ("unknown", "".to_string()),
+            ("invalid", "".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_ows() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings("gzip\r\n , \r\n br");
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("\r\n\tgzip\r\n\r\n \t,\r\n\r\n\t br \r\n");
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_bytes(b"gzip\r\n , \r\n br");
+        assert_eq!(ce.next().unwrap(), RawContentEncoding::Std(Brotli));
+        assert_eq!(ce.next().unwrap(), RawContentEncoding::Std(Gzip));
+        assert!(ce.next().is_none());
+
+        // Only SP, HTAB, CR, and LF are whitespace in headers.
+        assert_eq!(ContentEncoding::new("\u{a0}gzip"), Other("\u{a0}gzip"));
+        assert_eq!(ContentEncoding::new("gzip\u{2003}"), Other("gzip\u{2003}"));
+        assert_eq!(ContentEncoding::new("\x0cgzip"), Other("\x0cgzip"));
+        assert_eq!(RawContentEncoding::new(b"\x0cgzip"), RawContentEncoding::Other(b"\x0cgzip"));
+        assert_eq!(RawContentEncoding::new(b" \r\n\t"), RawContentEncoding::Std(Identity));
+    }
+
+    #[test]
+    fn test_ces() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings("deflate, br, identity");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Deflate));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("identity");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("\t\t,,            ,     ,");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("Br, exi,pack200-GZip   ");
+        assert_eq!(ce.next().unwrap(), Std(Pack200Gzip));
+        assert_eq!(ce.next().unwrap(), Std(EfficientXML));
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("\t\t\t   gzip");
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("\tabc\t\t, def  ");
+        assert_eq!(ce.next().unwrap(), Other("def"));
+        assert_eq!(ce.next().unwrap(), Other("abc"));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_applied() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings_applied("deflate, br, identity");
+        assert_eq!(ce.next().unwrap(), Std(Deflate));
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("identity");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("\t\t,,            ,     ,");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("Br, exi,pack200-GZip   ");
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(EfficientXML));
+        assert_eq!(ce.next().unwrap(), Std(Pack200Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("\t\t\t   gzip");
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_applied("\tabc\t\t, def  ");
+        assert_eq!(ce.next().unwrap(), Other("abc"));
+        assert_eq!(ce.next().unwrap(), Other("def"));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_opt() {
+        use self::StdContentEncoding::*;
+
+        assert!(content_encodings_opt(None).next().is_none());
+
+        let mut ce = content_encodings_opt(Some(""));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_opt(Some("gzip"));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_opt(Some("gzip, br"));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Brotli));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Gzip));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_multi() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(content_encodings_multi(vec!["gzip", "br"]).collect::<Vec<_>>(),
+                   vec![Std(Brotli), Std(Gzip)]);
+        assert_eq!(content_encodings_multi(["deflate, custom", " zstd , br"].iter().cloned())
+                       .collect::<Vec<_>>(),
+                   content_encodings("deflate, custom, zstd , br").collect::<Vec<_>>());
+        assert_eq!(content_encodings_multi(vec!["gzip,", "br"]).collect::<Vec<_>>(),
+                   vec![Std(Brotli), Std(Identity), Std(Gzip)]);
+        assert_eq!(content_encodings_multi(vec!["gzip", ""]).collect::<Vec<_>>(),
+                   vec![Std(Identity), Std(Gzip)]);
+        assert_eq!(content_encodings_multi(vec!["gzip, br"]).collect::<Vec<_>>(),
+                   vec![Std(Brotli), Std(Gzip)]);
+        assert!(content_encodings_multi(Vec::<&str>::new()).next().is_none());
+    }
+
+    #[test]
+    fn test_ces_from_parts() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let expected = vec![Std(Brotli), Std(Gzip)];
+        assert_eq!(content_encodings_from_parts(&["gzip", "br"]).collect::<Vec<_>>(), expected);
+        assert_eq!(content_encodings_from_parts(&["gzip, ", "br"]).collect::<Vec<_>>(),
+                   expected);
+        assert_eq!(content_encodings_from_parts(&["gzip", " , br"]).collect::<Vec<_>>(),
+                   expected);
+        assert_eq!(content_encodings_from_parts(&["gzip, br"]).collect::<Vec<_>>(), expected);
+
+        assert_eq!(content_encodings_from_parts(&["deflate, Custom", "zstd,", "br"])
+                       .collect::<Vec<_>>(),
+                   vec![Std(Brotli), Std(Zstd), Other("Custom"), Std(Deflate)]);
+        assert_eq!(content_encodings_from_parts(&["gzip,", "br,"]).collect::<Vec<_>>(),
+                   content_encodings("gzip, br,").collect::<Vec<_>>());
+        assert_eq!(content_encodings_from_parts(&[""]).collect::<Vec<_>>(), vec![Std(Identity)]);
+        assert!(content_encodings_from_parts(&[]).next().is_none());
+    }
+
+    #[test]
+    fn test_ces_skip_empty() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(content_encodings("gzip,,br,").collect::<Vec<_>>(),
+                   vec![Std(Identity), Std(Brotli), Std(Identity), Std(Gzip)]);
+        assert_eq!(content_encodings_skip_empty("gzip,,br,").collect::<Vec<_>>(),
+                   vec![Std(Brotli), Std(Gzip)]);
+
+        let mut ce = content_encodings_skip_empty(" , custom ,\t, identity ,");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next_back().unwrap(), Other("custom"));
+        assert!(ce.next().is_none());
+
+        assert!(content_encodings_skip_empty("").next().is_none());
+        assert!(content_encodings_skip_empty(" ,\t, ").next().is_none());
+    }
+
+    #[test]
+    fn test_ces_from() {
+        use std::borrow::Cow;
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let expected = vec![Std(Brotli), Other("Custom"), Std(Gzip)];
+
+        let owned = String::from("gzip, Custom, br");
+        assert_eq!(content_encodings_from(&owned).collect::<Vec<_>>(), expected);
+
+        let borrowed = "gzip, Custom, br";
+        assert_eq!(content_encodings_from(borrowed).collect::<Vec<_>>(), expected);
+        assert_eq!(content_encodings_from(&borrowed).collect::<Vec<_>>(), expected);
+
+        let cow: Cow<str> = Cow::Borrowed(borrowed);
+        assert_eq!(content_encodings_from(&cow).collect::<Vec<_>>(), expected);
+
+        let cow: Cow<str> = Cow::Owned(owned.clone());
+        assert_eq!(content_encodings_from(&cow).collect::<Vec<_>>(), expected);
+        assert_eq!(content_encodings_from(&cow).len(), 3);
+    }
+
+    #[test]
+    fn test_parse_with_extensions() {
+        use self::StdContentEncoding::*;
+        use self::ExtendedEncoding::*;
+
+        const BZIP2: u16 = 1;
+        const LZ4: u16 = 2;
+
+        let extra = [("bzip2", BZIP2), ("x-bzip2", BZIP2), ("lz4", LZ4), ("gzip", 99)];
+
+        assert_eq!(parse_with_extensions("gzip, BZIP2 , custom, x-bzip2, lz4", &extra)
+                       .collect::<Vec<_>>(),
+                   vec![Extension(LZ4), Extension(BZIP2), Other("custom"), Extension(BZIP2),
+                        Std(Gzip)]);
+        assert_eq!(parse_with_extensions("bzip2", &[]).collect::<Vec<_>>(),
+                   vec![Other("bzip2")]);
+        assert_eq!(parse_with_extensions("", &extra).collect::<Vec<_>>(), vec![Std(Identity)]);
+    }
+
+    #[test]
+    fn test_ces_fused() {
+        fn fused<I: core::iter::FusedIterator>(_: &I) {}
+
+        let mut ce = content_encodings("gzip, br");
+        fused(&ce);
+        assert_eq!(ce.by_ref().count(), 2);
+
+        for _ in 0..5 {
+            assert!(ce.next().is_none());
+            assert!(ce.next_back().is_none());
+            assert_eq!(ce.len(), 0);
+        }
+
+        let mut ce = content_encodings("");
+        assert_eq!(ce.next_back(), Some(ContentEncoding::Std(StdContentEncoding::Identity)));
+        assert!(ce.next().is_none());
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_marked() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(content_encodings_marked("deflate, custom, br").collect::<Vec<_>>(),
+                   vec![(Std(Brotli), false), (Other("custom"), false), (Std(Deflate), true)]);
+        assert_eq!(content_encodings_marked("gzip").collect::<Vec<_>>(),
+                   vec![(Std(Gzip), true)]);
+        assert_eq!(content_encodings_marked("").collect::<Vec<_>>(),
+                   vec![(Std(Identity), true)]);
+        assert_eq!(content_encodings_marked("gzip,").collect::<Vec<_>>(),
+                   vec![(Std(Identity), false), (Std(Gzip), true)]);
+
+        for s in &["gzip, br", "a, b, c, d", ",,", " identity "] {
+            assert_eq!(content_encodings_marked(s).filter(|&(_, last)| last).count(), 1);
+            assert_eq!(content_encodings_marked(s).last().map(|(e, _)| e),
+                       Some(innermost_encoding(s)));
+        }
+    }
+
+    #[test]
+    fn test_ces_double_ended() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings("gzip, abc, br, deflate");
+        assert_eq!(ce.next_back().unwrap(), Std(Gzip));
+        assert_eq!(ce.next().unwrap(), Std(Deflate));
+        assert_eq!(ce.next_back().unwrap(), Other("abc"));
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert!(ce.next().is_none());
+        assert!(ce.next_back().is_none());
+
+        let mut ce = content_encodings("identity");
+        assert_eq!(ce.next_back().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings("exi, zstd").rev();
+        assert_eq!(ce.next().unwrap(), Std(EfficientXML));
+        assert_eq!(ce.next().unwrap(), Std(Zstd));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_checked() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings_checked("gzip, x-custom, br");
+        assert_eq!(ce.next().unwrap(), Ok(Std(Brotli)));
+        assert_eq!(ce.next().unwrap(), Ok(Other("x-custom")));
+        assert_eq!(ce.next().unwrap(), Ok(Std(Gzip)));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_checked("gzip, bad token, br;q=1,, \"x\"");
+        assert_eq!(ce.next().unwrap().unwrap_err().token(), "\"x\"");
+        assert_eq!(ce.next().unwrap(), Ok(Std(Identity)));
+        assert_eq!(ce.next().unwrap().unwrap_err().token(), "br;q=1");
+        assert_eq!(ce.next().unwrap().unwrap_err().token(), "bad token");
+        assert_eq!(ce.next().unwrap(), Ok(Std(Gzip)));
+        assert!(ce.next().is_none());
+
+        assert!(content_encodings_checked("ÆØ, gzip").any(|r| r.is_err()));
+        assert!(content_encodings_checked("deflate, gzip").all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn test_segments() {
+        use self::StdContentEncoding::*;
+        use self::Segment::*;
+
+        let mut ce = content_encoding_segments("gzip, x-custom, bad token, ,\"q\", BR");
+        assert_eq!(ce.next().unwrap(), Known(Brotli));
+        assert_eq!(ce.next().unwrap(), Invalid("\"q\""));
+        assert_eq!(ce.next().unwrap(), Known(Identity));
+        assert_eq!(ce.next().unwrap(), Invalid("bad token"));
+        assert_eq!(ce.next().unwrap(), Unknown("x-custom"));
+        assert_eq!(ce.next().unwrap(), Known(Gzip));
+        assert!(ce.next().is_none());
+
+        assert_eq!(Segment::new(" x-gzip "), Known(Gzip));
+        assert_eq!(Segment::new("Custom"), Unknown("Custom"));
+        assert_eq!(Segment::new("gzip;q=1"), Invalid("gzip;q=1"));
+        assert_eq!(Segment::new("ÆØ"), Invalid("ÆØ"));
+    }
+
+    #[test]
+    fn test_ces_verbatim() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(content_encodings_verbatim("GZIP").collect::<Vec<_>>(),
+                   vec![(Std(Gzip), "GZIP")]);
+        assert_eq!(content_encodings_verbatim(" x-Gzip ,Custom,\tBr ,").collect::<Vec<_>>(),
+                   vec![(Std(Identity), ""), (Std(Brotli), "Br"), (Other("Custom"), "Custom"),
+                        (Std(Gzip), "x-Gzip")]);
+
+        let header = "deflate , IDENTITY, zstd";
+        let verbatim = content_encodings_verbatim(header).collect::<Vec<_>>();
+        let spanned = content_encodings_spanned(header).collect::<Vec<_>>();
+        assert_eq!(verbatim.len(), spanned.len());
+
+        for (&(enc, token), &(span_enc, ref range)) in verbatim.iter().zip(&spanned) {
+            assert_eq!(enc, span_enc);
+            assert_eq!(token, &header[range.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_ces_spanned() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let header = " gzip, br ";
+        let mut ce = content_encodings_spanned(header);
+        assert_eq!(ce.next().unwrap(), (Std(Brotli), 7..9));
+        assert_eq!(ce.next().unwrap(), (Std(Gzip), 1..5));
+        assert!(ce.next().is_none());
+        assert_eq!(&header[7..9], "br");
+        assert_eq!(&header[1..5], "gzip");
+
+        let header = "\tABC\t,, ❤x , identity";
+        let mut ce = content_encodings_spanned(header);
+        assert_eq!(ce.next().unwrap(), (Std(Identity), 15..23));
+        assert_eq!(ce.next().unwrap(), (Other("❤x"), 8..12));
+        assert_eq!(ce.next().unwrap(), (Std(Identity), 6..6));
+        assert_eq!(ce.next().unwrap(), (Other("ABC"), 1..4));
+        assert!(ce.next().is_none());
+        assert_eq!(&header[8..12], "❤x");
+
+        let mut ce = content_encodings_spanned("");
+        assert_eq!(ce.next().unwrap(), (Std(Identity), 0..0));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_spanned("  ");
+        assert_eq!(ce.next().unwrap(), (Std(Identity), 0..0));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_len() {
+        let mut ce = content_encodings("gzip, abc, br, deflate");
+        assert_eq!(ce.len(), 4);
+        assert_eq!(ce.size_hint(), (4, Some(4)));
+        ce.next();
+        assert_eq!(ce.len(), 3);
+        ce.next_back();
+        assert_eq!(ce.len(), 2);
+        assert_eq!(ce.size_hint(), (2, Some(2)));
+        ce.next();
+        ce.next();
+        assert_eq!(ce.len(), 0);
+        assert!(ce.next().is_none());
+        assert_eq!(ce.len(), 0);
+        assert_eq!(ce.size_hint(), (0, Some(0)));
+
+        assert_eq!(content_encodings("").len(), 1);
+        assert_eq!(content_encodings(",,").len(), 3);
+        assert_eq!(content_encodings("ÆØ,Б,Д❤").len(), 3);
+        assert_eq!(content_encodings_applied("gzip, br").len(), 2);
+
+        let mut buf = [ContentEncoding::new(""); 4];
+        let ce = content_encodings("gzip, br");
+        assert!(ce.len() <= buf.len());
+        for (slot, enc) in buf.iter_mut().zip(ce) {
+            *slot = enc;
+        }
+        assert_eq!(buf[0], ContentEncoding::new("br"));
+        assert_eq!(buf[1], ContentEncoding::new("gzip"));
+    }
+
+    #[test]
+    fn test_ces_peekable() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings("gzip, custom, br").peekable();
+        assert_eq!(ce.peek(), Some(&Std(Brotli)));
+        assert_eq!(ce.peek(), Some(&Std(Brotli)));
+        assert_eq!(ce.len(), 3);
+        assert_eq!(ce.next(), Some(Std(Brotli)));
+        assert_eq!(ce.peek(), Some(&Other("custom")));
+        assert_eq!(ce.next_back(), Some(Std(Gzip)));
+        assert_eq!(ce.next(), Some(Other("custom")));
+        assert_eq!(ce.peek(), None);
+
+        let mut ce = content_encodings("deflate, zstd").peekable();
+        if let Some(&Std(Zstd)) = ce.peek() {
+            ce.next();
+        }
+        assert_eq!(ce.collect::<Vec<_>>(), vec![Std(Deflate)]);
+
+        let header = format!("{}gzip", "br, ".repeat(10_000));
+        let mut ce = content_encodings(&header).peekable();
+        assert_eq!(ce.peek(), Some(&Std(Gzip)));
+        assert_eq!(ce.len(), 10_001);
+    }
+
+    #[test]
+    fn test_ces_named() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        struct Layers<'a> {
+            decode: ContentEncodings<'a>,
+            encode: core::iter::Rev<ContentEncodings<'a>>,
+        }
+
+        fn layers<'a>(s: &'a str) -> Layers<'a> {
+            Layers {
+                decode: content_encodings(s),
+                encode: content_encodings_applied(s),
+            }
+        }
+
+        let mut l = layers("gzip, br");
+        assert_eq!(l.decode.next().unwrap(), Std(Brotli));
+        assert_eq!(l.encode.next().unwrap(), Std(Gzip));
+        assert_eq!(l.decode.clone().next().unwrap(), Std(Gzip));
+        assert_eq!(l.decode.next().unwrap(), Std(Gzip));
+        assert!(l.decode.next().is_none());
+        assert_eq!(l.encode.next().unwrap(), Std(Brotli));
+        assert!(l.encode.next().is_none());
+        assert_eq!(layers("abc").decode.next().unwrap(), Other("abc"));
+    }
+
+    #[test]
+    fn test_accept_encodings() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ae = accept_encodings("gzip;q=0.8, br;q=1.0, *;q=0");
+        assert_eq!(ae.next().unwrap(), (Std(Gzip), 0.8));
+        assert_eq!(ae.next().unwrap(), (Std(Brotli), 1.0));
+        assert_eq!(ae.next().unwrap(), (Other("*"), 0.0));
+        assert!(ae.next().is_none());
+
+        let mut ae = accept_encodings("  GZIP , deflate ; Q = 0.5 ,identity;foo=bar;q=0.25");
+        assert_eq!(ae.next().unwrap(), (Std(Gzip), 1.0));
+        assert_eq!(ae.next().unwrap(), (Std(Deflate), 0.5));
+        assert_eq!(ae.next().unwrap(), (Std(Identity), 0.25));
+        assert!(ae.next().is_none());
+
+        let mut ae = accept_encodings("zstd;q=2, custom;q=-1, br;q=, exi;q=nan, gzip;q");
+        assert_eq!(ae.next().unwrap(), (Std(Zstd), 1.0));
+        assert_eq!(ae.next().unwrap(), (Other("custom"), 0.0));
+        assert_eq!(ae.next().unwrap(), (Std(Brotli), 0.0));
+        assert_eq!(ae.next().unwrap(), (Std(EfficientXML), 0.0));
+        assert_eq!(ae.next().unwrap(), (Std(Gzip), 0.0));
+        assert!(ae.next().is_none());
+
+        let mut ae = accept_encodings("a;q=inf, b;q=1e-1, c;q=+.5, d;q=0.1234, e;q=1.001, f;q=.5");
+        assert_eq!(ae.next().unwrap().1, 1.0);
+        assert_eq!(ae.next().unwrap().1, 0.1);
+        assert_eq!(ae.next().unwrap().1, 0.5);
+        assert_eq!(ae.next().unwrap().1, 0.1234);
+        assert_eq!(ae.next().unwrap().1, 1.0);
+        assert_eq!(ae.next().unwrap().1, 0.5);
+        assert!(ae.next().is_none());
+
+        assert_eq!(accept_encodings("gzip;q=1.5").collect::<Vec<_>>(), vec![(Std(Gzip), 1.0)]);
+        assert_eq!(accept_encodings("gzip;q=-0.5").collect::<Vec<_>>(), vec![(Std(Gzip), 0.0)]);
+
+        let mut ae = accept_encodings("a;q=0, b;q=0., c;q=0.125, d;q=0.5, e;q=1., f;q=1.000");
+        assert_eq!(ae.next().unwrap().1, 0.0);
+        assert_eq!(ae.next().unwrap().1, 0.0);
+        assert_eq!(ae.next().unwrap().1, 0.125);
+        assert_eq!(ae.next().unwrap().1, 0.5);
+        assert_eq!(ae.next().unwrap().1, 1.0);
+        assert_eq!(ae.next().unwrap().1, 1.0);
+        assert!(ae.next().is_none());
+
+        let mut ae = accept_encodings(", ,gzip,,");
+        assert_eq!(ae.next().unwrap(), (Std(Gzip), 1.0));
+        assert!(ae.next().is_none());
+
+        assert!(accept_encodings("").next().is_none());
+        assert!(accept_encodings(" \t ").next().is_none());
+    }
+
+    #[test]
+    fn test_accept_encodings_recovering() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let ae = |s| accept_encodings_recovering(s, 1.0).collect::<Vec<_>>();
+
+        assert_eq!(ae("gzip;q=abc"), vec![(Std(Gzip), 1.0)]);
+        assert_eq!(ae("gzip;q=1.5"), vec![(Std(Gzip), 1.0)]);
+        assert_eq!(ae("gzip;q=-0.1"), vec![(Std(Gzip), 0.0)]);
+        assert_eq!(ae("gzip;q=0.1234, br;q=nan, zstd;q=, custom;q"), vec![
+            (Std(Gzip), 0.1234),
+            (Std(Brotli), 1.0),
+            (Std(Zstd), 1.0),
+            (Other("custom"), 1.0),
+        ]);
+        assert_eq!(ae("gzip;q=0.5, br, , *;q=0"),
+                   accept_encodings("gzip;q=0.5, br, , *;q=0").collect::<Vec<_>>());
+
+        assert_eq!(accept_encodings_recovering("gzip;q=abc", 0.5).collect::<Vec<_>>(),
+                   vec![(Std(Gzip), 0.5)]);
+        assert_eq!(accept_encodings("gzip;q=abc").collect::<Vec<_>>(), vec![(Std(Gzip), 0.0)]);
+    }
+
+    #[test]
+    fn test_accept_encodings_strict() {
+        use self::StdContentEncoding::*;
+
+        let ae = |s| accept_encodings_strict(s).collect::<Vec<_>>();
+
+        assert_eq!(ae("gzip;q=abc"), vec![Err(InvalidQvalueError { value: "abc" })]);
+        assert_eq!(ae("gzip;q=1.5"), vec![Err(InvalidQvalueError { value: "1.5" })]);
+        assert_eq!(ae("gzip;q=-0.1"), vec![Err(InvalidQvalueError { value: "-0.1" })]);
+        assert_eq!(ae("br;q=0.5, gzip; q = 2 , zstd"), vec![
+            Ok((ContentEncoding::Std(Brotli), 0.5)),
+            Err(InvalidQvalueError { value: "2" }),
+            Ok((ContentEncoding::Std(Zstd), 1.0)),
+        ]);
+        assert_eq!(ae("gzip;q"), vec![Err(InvalidQvalueError { value: "" })]);
+        assert!(ae(", ,").is_empty());
+
+        let err = ae("gzip;q=abc")[0].unwrap_err();
+        assert_eq!(err.value(), "abc");
+        assert_eq!(err.to_string(), "invalid quality value \"abc\"");
+    }
+
+    #[test]
+    fn test_accepts() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert!(accepts("*, identity;q=0", Std(Gzip)));
+        assert!(accepts("*, identity;q=0", Other("custom")));
+        assert!(!accepts("*, identity;q=0", Std(Identity)));
+        assert!(!accepts("identity;q=0", Std(Identity)));
+        assert!(!accepts("*;q=0", Std(Identity)));
+        assert!(accepts("*;q=0, identity", Std(Identity)));
+
+        assert!(!accepts("gzip;q=0, br", Std(Gzip)));
+        assert!(!accepts("x-gzip;q=0, *", Std(Gzip)));
+        assert!(!accepts("GZIP; Q=0.000, *", Other("gzip")));
+        assert!(accepts("gzip;q=0.001", Std(Gzip)));
+        assert!(accepts("gzip, gzip;q=0", Std(Gzip)));
+        assert!(accepts("gzip;q=2", Std(Gzip)));
+        assert!(!accepts("gzip;q=-1", Std(Gzip)));
+
+        assert!(accepts("gzip, *;q=0.5", Std(Brotli)));
+        assert!(!accepts("gzip, *;q=0", Std(Brotli)));
+        assert!(accepts("gzip, *;q=0", Std(Gzip)));
+        assert!(accepts("br, Custom", Other("custom")));
+
+        assert!(!accepts("gzip", Std(Brotli)));
+        assert!(!accepts("gzip", Other("custom")));
+        assert!(accepts("gzip", Std(Identity)));
+        assert!(accepts("", Std(Identity)));
+        assert!(!accepts("", Std(Gzip)));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        use self::StdContentEncoding::*;
+
+        let supported = [Brotli, Gzip, Zstd];
+
+        assert_eq!(negotiate("gzip, br;q=0.8", &supported), Some(Gzip));
+        assert_eq!(negotiate("gzip;q=0.5, br;q=0.8, zstd;q=0.9", &supported), Some(Zstd));
+        assert_eq!(negotiate("gzip, br, zstd", &supported), Some(Brotli));
+        assert_eq!(negotiate("zstd, gzip, br", &supported), Some(Brotli));
+        assert_eq!(negotiate("GZIP, x-gzip;q=0", &supported), Some(Gzip));
+        assert_eq!(negotiate("gzip;q=0.5, identity", &supported), Some(Identity));
+        assert_eq!(negotiate("gzip;q=0.5, identity;q=0.5", &supported), Some(Gzip));
+
+        // Wildcard acceptance.
+        assert_eq!(negotiate("*", &supported), Some(Brotli));
+        assert_eq!(negotiate("*;q=0.5, gzip", &supported), Some(Gzip));
+        assert_eq!(negotiate("*, br;q=0", &supported), Some(Gzip));
+        assert_eq!(negotiate("*", &[]), Some(Identity));
+
+        // Explicit exclusion.
+        assert_eq!(negotiate("gzip;q=0, br;q=0", &[Brotli, Gzip]), Some(Identity));
+        assert_eq!(negotiate("gzip;q=0, identity;q=0", &[Gzip]), None);
+        assert_eq!(negotiate("*;q=0", &supported), None);
+        assert_eq!(negotiate("*;q=0, identity", &supported), Some(Identity));
+        assert_eq!(negotiate("*;q=0, zstd;q=0.1", &supported), Some(Zstd));
+        assert_eq!(negotiate("identity;q=0", &supported), None);
+
+        // No overlap.
+        assert_eq!(negotiate("deflate, compress", &supported), Some(Identity));
+        assert_eq!(negotiate("", &supported), Some(Identity));
+        assert_eq!(negotiate("deflate, identity;q=0", &supported), None);
+        assert_eq!(negotiate("gzip", &[]), Some(Identity));
+        assert_eq!(negotiate("gzip", &[Identity, Gzip]), Some(Gzip));
+    }
+
+    #[test]
+    fn test_preferred_encodings() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(preferred_encodings("identity;q=0.5, gzip, br;q=0.9"), vec![
+            (Std(Gzip), 1.0),
+            (Std(Brotli), 0.9),
+            (Std(Identity), 0.5),
+        ]);
+
+        assert_eq!(preferred_encodings("deflate;q=0.5, gzip;q=0, br;q=0.5, zstd, *;q=0"), vec![
+            (Std(Zstd), 1.0),
+            (Std(Deflate), 0.5),
+            (Std(Brotli), 0.5),
+        ]);
+
+        assert_eq!(preferred_encodings("abc;q=0.1, gzip;q=0.001, *"), vec![
+            (Other("*"), 1.0),
+            (Other("abc"), 0.1),
+            (Std(Gzip), 0.001),
+        ]);
+
+        assert!(preferred_encodings("").is_empty());
+        assert!(preferred_encodings("gzip;q=0, identity;q=0").is_empty());
+    }
+
+    #[test]
+    fn test_ces_lenient() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(ParsedEncoding::new("gzip; level=9"), ParsedEncoding {
+            encoding: Std(Gzip),
+            params: Some("level=9"),
+        });
+        assert_eq!(ParsedEncoding::new(" br "), ParsedEncoding {
+            encoding: Std(Brotli),
+            params: None,
+        });
+        assert_eq!(ParsedEncoding::new("abc;"), ParsedEncoding {
+            encoding: Other("abc"),
+            params: Some(""),
+        });
+
+        let mut ce = content_encodings_lenient("gzip; level=9, br");
+        assert_eq!(ce.next().unwrap(), ParsedEncoding { encoding: Std(Brotli), params: None });
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Gzip),
+            params: Some("level=9"),
+        });
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_lenient(r#"deflate;a="x,\"y", zstd ; b=1;c="," "#);
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Zstd),
+            params: Some(r#"b=1;c=",""#),
+        });
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Deflate),
+            params: Some(r#"a="x,\"y""#),
+        });
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_lenient("");
+        assert_eq!(ce.next().unwrap(), ParsedEncoding { encoding: Std(Identity), params: None });
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_lenient("br,,");
+        assert_eq!(ce.next().unwrap().encoding, Std(Identity));
+        assert_eq!(ce.next().unwrap().encoding, Std(Identity));
+        assert_eq!(ce.next().unwrap().encoding, Std(Brotli));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_lenient(r#"gzip, abc;x="unterminated, br"#);
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Other("abc"),
+            params: Some(r#"x="unterminated, br"#),
+        });
+        assert_eq!(ce.next().unwrap().encoding, Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_lenient(r#"br;a="\\", gzip;b=\", zstd;c=d\, e"#);
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Zstd),
+            params: Some(r#"c=d\, e"#),
+        });
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Gzip),
+            params: Some(r#"b=\""#),
+        });
+        assert_eq!(ce.next().unwrap(), ParsedEncoding {
+            encoding: Std(Brotli),
+            params: Some(r#"a="\\""#),
+        });
+        assert!(ce.next().is_none());
+
+        let s = r#"gzip;a="b,c","#.repeat(20_000);
+        assert_eq!(content_encodings_lenient(&s).count(), 20_001);
+    }
+
+    #[test]
+    fn test_transfer_encodings() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+        use self::TransferEncoding::*;
+
+        assert_eq!(TransferEncoding::new("chunked"), Chunked);
+        assert_eq!(TransferEncoding::new("\t ChUnKeD "), Chunked);
+        assert_eq!(TransferEncoding::new("gzip"), Content(Std(Gzip)));
+        assert_eq!(TransferEncoding::new("trailers"), Content(Other("trailers")));
+        assert!(Chunked.is_chunked());
+        assert!(!Content(Std(Gzip)).is_chunked());
+
+        let mut te = transfer_encodings("gzip, chunked");
+        assert_eq!(te.next().unwrap(), Chunked);
+        assert_eq!(te.next().unwrap(), Content(Std(Gzip)));
+        assert!(te.next().is_none());
+
+        let mut te = transfer_encodings("chunked");
+        assert_eq!(te.next().unwrap(), Chunked);
+        assert!(te.next().is_none());
+
+        let mut te = transfer_encodings("Deflate, x-gzip, Chunked");
+        assert_eq!(te.next_back().unwrap(), Content(Std(Deflate)));
+        assert_eq!(te.next().unwrap(), Chunked);
+        assert_eq!(te.next().unwrap(), Content(Std(Gzip)));
+        assert!(te.next().is_none());
+
+        assert!(transfer_encodings("gzip, chunked").next().unwrap().is_chunked());
+        assert!(!transfer_encodings("chunked, gzip").next().unwrap().is_chunked());
+    }
+
+    #[test]
+    fn test_ord() {
+        use std::collections::BTreeSet;
+        use self::StdContentEncoding::*;
+
+        assert!(Brotli < Gzip);
+        assert!(Identity > Deflate);
+
+        let set = content_encodings("gzip, br, gzip, identity, abc, br")
+            .filter_map(|e| e.as_std())
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&Gzip));
+        assert!(set.contains(&Brotli));
+        assert!(set.contains(&Identity));
+        assert!(!set.contains(&Deflate));
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), vec![Brotli, Gzip, Identity]);
+    }
+
+    #[test]
+    fn test_ces_bytes() {
+        use self::StdContentEncoding::*;
+        use self::RawContentEncoding::*;
+
+        assert_eq!(RawContentEncoding::new(b"\t\t\rBr  "), Std(Brotli));
+        assert_eq!(RawContentEncoding::new(b"  x-GZIP "), Std(Gzip));
+        assert_eq!(RawContentEncoding::new(b""), Std(Identity));
+        assert_eq!(RawContentEncoding::new(b" \t "), Std(Identity));
+        assert_eq!(RawContentEncoding::new(b" abc "), Other(b"abc"));
+        assert_eq!(RawContentEncoding::new(b"\xff\xfe"), Other(b"\xff\xfe"));
+
+        let mut ce = content_encodings_bytes(b"deflate, br, identity");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Deflate));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_bytes(b"gzip, caf\xe9 ,, \xc3\x28");
+        assert_eq!(ce.next().unwrap(), Other(b"\xc3\x28"));
+        assert_eq!(ce.next_back().unwrap(), Std(Gzip));
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert_eq!(ce.next().unwrap(), Other(b"caf\xe9"));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_bytes(b"");
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        assert_eq!(StdContentEncoding::from_bytes(b"PACK200-gzip"), Some(Pack200Gzip));
+        assert_eq!(StdContentEncoding::from_bytes(b"\xffgzip"), None);
+    }
+
+    #[test]
+    fn test_ces_bytes_httparse() {
+        use self::StdContentEncoding::*;
+        use self::RawContentEncoding::*;
+
+        // Header as yielded by `httparse`, with a name and raw value.
+        struct Header<'a> {
+            name: &'a str,
+            value: &'a [u8],
+        }
+
+        let headers = [
+            Header { name: "Content-Type", value: b"text/html" },
+            Header { name: "Content-Encoding", value: b"gzip, X-Custom,br" },
+        ];
+
+        let value = headers.iter()
+            .find(|h| h.name.eq_ignore_ascii_case("content-encoding"))
+            .unwrap()
+            .value;
+
+        let encs = content_encodings_bytes(value).collect::<Vec<_>>();
+        assert_eq!(encs, vec![Std(Brotli), Other(b"X-Custom"), Std(Gzip)]);
+        assert_eq!(encs[1].to_utf8(), Ok(ContentEncoding::Other("X-Custom")));
+        assert_eq!(encs[2].to_utf8(), Ok(ContentEncoding::Std(Gzip)));
+        assert!(Other(b"caf\xe9").to_utf8().is_err());
+
+        // Commas are found across the chunks scanned at once.
+        for len in 0..200 {
+            let mut value = vec![b'a'; len];
+
+            for i in (0..len).step_by(7 + len % 61) {
+                value[i] = b',';
+            }
+
+            let expected = value.split(|&b| b == b',').rev()
+                .map(RawContentEncoding::new)
+                .collect::<Vec<_>>();
+
+            assert_eq!(content_encodings_bytes(&value).collect::<Vec<_>>(), expected);
+            assert_eq!(content_encodings_bytes(&value).rev().collect::<Vec<_>>(),
+                       expected.iter().rev().cloned().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_ces_header() {
+        use http::HeaderValue;
+        use self::StdContentEncoding::*;
+        use self::RawContentEncoding::*;
+
+        let value = HeaderValue::from_static("gzip, X-Custom,br");
+        let mut ce = content_encodings_header(&value);
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Other(b"X-Custom"));
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let value = HeaderValue::from_bytes(b"deflate,\tcaf\xe9\t").unwrap();
+        assert!(value.to_str().is_err());
+        let mut ce = content_encodings_header(&value);
+        assert_eq!(ce.next().unwrap(), Other(b"caf\xe9"));
+        assert_eq!(ce.next().unwrap(), Std(Deflate));
+        assert!(ce.next().is_none());
+
+        let value = HeaderValue::from_static("");
+        let mut ce = content_encodings_header(&value);
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_ces_limited() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings_limited("gzip, br", 2).unwrap();
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = content_encodings_limited("", 1).unwrap();
+        assert_eq!(ce.next().unwrap(), Std(Identity));
+        assert!(ce.next().is_none());
+
+        let err = content_encodings_limited("gzip, br, abc", 2).unwrap_err();
+        assert_eq!(err.layers(), 3);
+        assert_eq!(err.max(), 2);
+        assert_eq!(err.to_string(), "3 content encoding layers exceeds limit of 2");
+
+        let err = content_encodings_limited(",,", 2).unwrap_err();
+        assert_eq!(err.layers(), 3);
+
+        let header = vec!["gzip"; 10_000].join(", ");
+        assert_eq!(content_encodings_limited(&header, 16).unwrap_err().layers(), 10_000);
+        assert_eq!(content_encodings_limited(&header, 10_000).unwrap().count(), 10_000);
+        assert!(content_encodings_limited("gzip", 0).is_err());
+        assert_eq!(Other("abc"), content_encodings_limited("abc", 1).unwrap().next().unwrap());
+    }
+
+    #[test]
+    fn test_validate() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(validate_content_encoding("gzip, br"), Ok(()));
+        assert_eq!(validate_content_encoding("gzip"), Ok(()));
+        assert_eq!(validate_content_encoding("identity"), Ok(()));
+        assert_eq!(validate_content_encoding(""), Ok(()));
+        assert_eq!(validate_content_encoding("abc, abd"), Ok(()));
+        assert_eq!(validate_content_encoding("gzip,"), Ok(()));
+        assert_eq!(validate_content_encoding(" , gzip, , br,"), Ok(()));
+        assert_eq!(validate_content_encoding(", identity"), Ok(()));
+
+        assert_eq!(validate_content_encoding("gzip, identity"),
+                   Err(EncodingError::IdentityOutermost));
+        assert_eq!(validate_content_encoding("identity, gzip"),
+                   Err(EncodingError::IdentityInnermost));
+        assert_eq!(validate_content_encoding("identity, custom"),
+                   Err(EncodingError::IdentityInnermost));
+        assert_eq!(validate_content_encoding("gzip, , identity"),
+                   Err(EncodingError::IdentityOutermost));
+        assert_eq!(validate_content_encoding("identity, gzip, ,"),
+                   Err(EncodingError::IdentityInnermost));
+        assert_eq!(validate_content_encoding("gzip, identity, br"),
+                   Err(EncodingError::IdentityCombined));
+        assert_eq!(validate_content_encoding(" identity "), Ok(()));
+
+        assert_eq!(validate_content_encoding("gzip, gzip"),
+                   Err(EncodingError::Duplicate(Std(Gzip))));
+        assert_eq!(validate_content_encoding("gzip, br, x-gzip"),
+                   Err(EncodingError::Duplicate(Std(Gzip))));
+        assert_eq!(validate_content_encoding("Custom, br, cUSTOM"),
+                   Err(EncodingError::Duplicate(Other("cUSTOM"))));
+        assert_eq!(validate_content_encoding("identity, identity"),
+                   Err(EncodingError::Duplicate(Std(Identity))));
+        assert_eq!(validate_content_encoding("identity, gzip, gzip"),
+                   Err(EncodingError::Duplicate(Std(Gzip))));
+        assert_eq!(validate_content_encoding("gzip, gzip, identity"),
+                   Err(EncodingError::IdentityOutermost));
+
+        assert_eq!(validate_content_encoding("*"), Err(EncodingError::Wildcard));
+        assert_eq!(validate_content_encoding(" * "), Err(EncodingError::Wildcard));
+        assert_eq!(validate_content_encoding("gzip, *"), Err(EncodingError::Wildcard));
+        assert_eq!(validate_content_encoding("*, identity"), Err(EncodingError::Wildcard));
+        assert_eq!(validate_content_encoding("x*"), Ok(()));
+        assert_eq!(accept_encodings("*").next(), Some((Other("*"), 1.0)));
+
+        assert_eq!(validate_content_encoding("gzip;q=0.5"),
+                   Err(EncodingError::Parameter("gzip;q=0.5")));
+        assert_eq!(validate_content_encoding("br, gzip ; q=1 "),
+                   Err(EncodingError::Parameter("gzip ; q=1")));
+        assert_eq!(validate_content_encoding("custom;foo=bar"),
+                   Err(EncodingError::Parameter("custom;foo=bar")));
+        assert_eq!(validate_content_encoding("identity;q=0"),
+                   Err(EncodingError::Parameter("identity;q=0")));
+        assert_eq!(accept_encodings("gzip;q=0.5").next(), Some((Std(Gzip), 0.5)));
+        assert_eq!(accept_encodings("br, gzip ; q=1 ").collect::<Vec<_>>(),
+                   vec![(Std(Brotli), 1.0), (Std(Gzip), 1.0)]);
+
+        assert_eq!(EncodingError::IdentityCombined.to_string(),
+                   "identity combined with other content encodings");
+        assert_eq!(EncodingError::IdentityInnermost.to_string(),
+                   "identity as the innermost of multiple content encodings");
+        assert_eq!(EncodingError::IdentityOutermost.to_string(),
+                   "identity as the outermost of multiple content encodings");
+        assert_eq!(EncodingError::Duplicate(Std(Gzip)).to_string(),
+                   "duplicate content encoding \"gzip\"");
+        assert_eq!(EncodingError::Wildcard.to_string(),
+                   "wildcard \"*\" used as a content encoding");
+        assert_eq!(EncodingError::Parameter("gzip;q=0.5").to_string(),
+                   "parameters used in content encoding \"gzip;q=0.5\"");
+    }
+
+    #[test]
+    fn test_parse_validated() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(parse_validated("gzip, Custom, br"),
+                   Ok(vec![Std(Brotli), Other("Custom"), Std(Gzip)]));
+        assert_eq!(parse_validated(" , gzip, ,"), Ok(vec![Std(Gzip)]));
+        assert_eq!(parse_validated(""), Ok(vec![]));
+        assert_eq!(parse_validated("identity"), Ok(vec![Std(Identity)]));
+
+        let err = ContentEncoding::new_strict("g(zip").unwrap_err();
+        assert_eq!(parse_validated("gzip, g(zip"), Err(EncodingError::InvalidToken(err)));
+        assert_eq!(parse_validated("gzip, g(zip").unwrap_err().to_string(), err.to_string());
+
+        let long = "x".repeat(DEFAULT_MAX_TOKEN_LEN + 1);
+        let err = parse_validated(&long).unwrap_err();
+        match err {
+            EncodingError::InvalidToken(err) =>
+                assert_eq!(err.kind(), ParseEncodingErrorKind::TooLong),
+            _ => panic!("unexpected error {:?}", err),
+        }
+
+        assert_eq!(parse_validated("gzip, *"), Err(EncodingError::Wildcard));
+        assert_eq!(parse_validated("gzip;q=1"), Err(EncodingError::Parameter("gzip;q=1")));
+        assert_eq!(parse_validated("identity, gzip"), Err(EncodingError::IdentityInnermost));
+        assert_eq!(parse_validated("gzip, x-gzip"), Err(EncodingError::Duplicate(Std(Gzip))));
+
+        // The problem in the outermost layer is reported first.
+        assert_eq!(parse_validated("g(zip, gzip, gzip"),
+                   Err(EncodingError::Duplicate(Std(Gzip))));
+        assert_eq!(parse_validated("gzip, gzip, g(zip"), Err(EncodingError::InvalidToken(
+            ContentEncoding::new_strict("g(zip").unwrap_err())));
+        assert_eq!(parse_validated("identity, g(zip"), Err(EncodingError::InvalidToken(
+            ContentEncoding::new_strict("g(zip").unwrap_err())));
+        assert_eq!(parse_validated("identity, br, *"), Err(EncodingError::Wildcard));
+        assert_eq!(parse_validated("a;b, *"), Err(EncodingError::Wildcard));
+        assert_eq!(parse_validated("*, a;b"), Err(EncodingError::Parameter("a;b")));
+        assert_eq!(parse_validated("gzip, identity, gzip"),
+                   Err(EncodingError::IdentityCombined));
+    }
+
+    #[test]
+    fn test_parse_all() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let header = "gzip, Custom, br, identity";
+        let all = parse_all(header);
+        assert_eq!(all, content_encodings(header).collect::<Vec<_>>());
+        assert_eq!(all, vec![Std(Identity), Std(Brotli), Other("Custom"), Std(Gzip)]);
+        assert_eq!(parse_all(""), vec![Std(Identity)]);
+    }
+
+    #[test]
+    fn test_decode_encode_order() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let header = "deflate, custom, br";
+        assert_eq!(decode_order(header), vec![Std(Brotli), Other("custom"), Std(Deflate)]);
+        assert_eq!(encode_order(header), vec![Std(Deflate), Other("custom"), Std(Brotli)]);
+
+        let mut rev = encode_order(header);
+        rev.reverse();
+        assert_eq!(rev, decode_order(header));
+
+        assert_eq!(decode_order("gzip"), encode_order("gzip"));
+        assert_eq!(decode_order(""), vec![Std(Identity)]);
+    }
+
+    #[test]
+    fn test_partition_supported() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let supported = [Gzip, Brotli];
+
+        assert_eq!(partition_supported("gzip, br", &supported),
+                   (vec![Std(Brotli), Std(Gzip)], vec![]));
+        assert_eq!(partition_supported("gzip, zstd, br", &supported),
+                   (vec![Std(Brotli)], vec![Std(Zstd), Std(Gzip)]));
+        assert_eq!(partition_supported("br, custom, identity, gzip", &supported),
+                   (vec![Std(Gzip), Std(Identity)], vec![Other("custom"), Std(Brotli)]));
+        assert_eq!(partition_supported("gzip, deflate", &supported),
+                   (vec![], vec![Std(Deflate), Std(Gzip)]));
+        assert_eq!(partition_supported("identity", &[]), (vec![Std(Identity)], vec![]));
+        assert_eq!(partition_supported("", &supported), (vec![Std(Identity)], vec![]));
+        assert_eq!(partition_supported("gzip", &[]), (vec![], vec![Std(Gzip)]));
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn test_parse_all_small() {
+        let header = "gzip, Custom, br, identity";
+        let small = parse_all_small(header);
+        assert!(!small.spilled());
+        assert_eq!(&small[..], &parse_all(header)[..]);
+
+        let header = "gzip, Custom, br, identity, deflate";
+        let small = parse_all_small(header);
+        assert!(small.spilled());
+        assert_eq!(&small[..], &parse_all(header)[..]);
+    }
+
+    #[test]
+    fn test_effective_encodings() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = effective_encodings("gzip, identity, br");
+        assert_eq!(ce.next().unwrap(), Std(Brotli));
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut ce = effective_encodings("identity,abc,, IDENTITY, gzip,");
+        assert_eq!(ce.next().unwrap(), Std(Gzip));
+        assert_eq!(ce.next().unwrap(), Other("abc"));
+        assert!(ce.next().is_none());
+
+        assert!(effective_encodings("").next().is_none());
+        assert!(effective_encodings("identity").next().is_none());
+        assert!(effective_encodings(" , identity ,,").next().is_none());
+    }
+
+    #[test]
+    fn test_count_layers() {
+        assert_eq!(count_layers("gzip"), 1);
+        assert_eq!(count_layers("gzip, br, custom"), 3);
+        assert_eq!(count_layers(""), 1);
+        assert_eq!(count_layers("gzip,"), 2);
+        assert_eq!(count_layers("gzip, identity, br,,"), 5);
+
+        assert_eq!(count_effective_layers("gzip"), 1);
+        assert_eq!(count_effective_layers("gzip, br, custom"), 3);
+        assert_eq!(count_effective_layers(""), 0);
+        assert_eq!(count_effective_layers("identity"), 0);
+        assert_eq!(count_effective_layers("gzip,"), 1);
+        assert_eq!(count_effective_layers("gzip, identity, br,,"), 2);
+    }
+
+    #[test]
+    fn test_innermost_encoding() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(innermost_encoding("gzip, br"), Std(Gzip));
+        assert_eq!(Some(innermost_encoding("gzip, br")), content_encodings("gzip, br").next_back());
+        assert_eq!(innermost_encoding(" Custom ,gzip,br"), Other("Custom"));
+        assert_eq!(innermost_encoding("zstd"), Std(Zstd));
+        assert_eq!(innermost_encoding(""), Std(Identity));
+        assert_eq!(innermost_encoding("identity"), Std(Identity));
+        assert_eq!(innermost_encoding(", br"), Std(Identity));
+    }
+
+    #[test]
+    fn test_outermost_encoding() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(outermost_encoding("gzip, br"), Std(Brotli));
+        assert_eq!(Some(outermost_encoding("gzip, br")), content_encodings("gzip, br").next());
+        assert_eq!(outermost_encoding("gzip,br , Custom "), Other("Custom"));
+        assert_eq!(outermost_encoding("deflate"), Std(Deflate));
+        assert_eq!(outermost_encoding(" DEFLATE\t"), Std(Deflate));
+        assert_eq!(outermost_encoding(""), Std(Identity));
+        assert_eq!(outermost_encoding("br,"), Std(Identity));
+    }
+
+    #[test]
+    fn test_strip_outer() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(strip_outer("gzip, custom, br"), Some((Std(Brotli), "gzip, custom")));
+        assert_eq!(strip_outer("gzip, custom"), Some((Other("custom"), "gzip")));
+        assert_eq!(strip_outer("gzip"), Some((Std(Gzip), "")));
+        assert_eq!(strip_outer(" GZIP\t"), Some((Std(Gzip), "")));
+        assert_eq!(strip_outer("gzip,"), Some((Std(Identity), "gzip")));
+        assert_eq!(strip_outer(""), None);
+        assert_eq!(strip_outer(" \t"), Some((Std(Identity), "")));
+        assert_eq!(strip_outer(" , a"), Some((Other("a"), " ")));
+        assert_eq!(strip_outer(" "), Some((Std(Identity), "")));
+
+        let mut header = " , a";
+        let mut layers = vec![];
+
+        while let Some((enc, rest)) = strip_outer(header) {
+            layers.push(enc);
+            header = rest;
+        }
+
+        assert_eq!(layers, content_encodings(" , a").collect::<Vec<_>>());
+
+        let mut header = "deflate, gzip, br";
+        let mut layers = vec![];
+
+        while let Some((enc, rest)) = strip_outer(header) {
+            assert_eq!(content_encodings(header).next(), Some(enc));
+            layers.push(enc);
+            header = rest;
+        }
+
+        assert_eq!(layers, content_encodings("deflate, gzip, br").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_dedup_consecutive() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(dedup_consecutive("gzip, gzip, br, br, br").collect::<Vec<_>>(),
+                   vec![(Std(Brotli), 3), (Std(Gzip), 2)]);
+        assert_eq!(dedup_consecutive("gzip, br, gzip").collect::<Vec<_>>(),
+                   vec![(Std(Gzip), 1), (Std(Brotli), 1), (Std(Gzip), 1)]);
+        assert_eq!(dedup_consecutive("x-gzip, GZIP, Custom, cUSTOM").collect::<Vec<_>>(),
+                   vec![(Other("cUSTOM"), 2), (Std(Gzip), 2)]);
+        assert_eq!(dedup_consecutive("").collect::<Vec<_>>(), vec![(Std(Identity), 1)]);
+        assert_eq!(dedup_consecutive(",,").collect::<Vec<_>>(), vec![(Std(Identity), 3)]);
+    }
+
+    #[test]
+    fn test_distinct_coding_count() {
+        assert_eq!(distinct_coding_count("gzip, GZIP, br"), 2);
+        assert_eq!(distinct_coding_count("identity, identity"), 0);
+        assert_eq!(distinct_coding_count(""), 0);
+        assert_eq!(distinct_coding_count("x-gzip, gzip"), 1);
+        assert_eq!(distinct_coding_count("Custom, br, custom, CUSTOM, other"), 3);
+        assert_eq!(distinct_coding_count("gzip, identity, , zstd, deflate"), 3);
+    }
+
+    #[test]
+    fn test_order_significant() {
+        assert!(!order_significant("identity"));
+        assert!(!order_significant(""));
+        assert!(!order_significant("gzip"));
+        assert!(!order_significant("identity, gzip, ,"));
+        assert!(!order_significant("custom"));
+
+        assert!(order_significant("gzip, br"));
+        assert!(order_significant("gzip, gzip"));
+        assert!(order_significant("gzip, identity, custom"));
+    }
+
+    #[test]
+    fn test_headers_equivalent() {
+        assert!(headers_equivalent("gzip, br", "gzip, br"));
+        assert!(headers_equivalent("GZIP , identity, Br", "gzip,br"));
+        assert!(headers_equivalent("x-gzip, X-Custom", "gzip, x-custom"));
+        assert!(headers_equivalent("", "identity"));
+        assert!(headers_equivalent(",,", ""));
+        assert!(headers_equivalent("identity, zstd,", "zstd"));
+
+        assert!(!headers_equivalent("gzip, br", "br, gzip"));
+        assert!(!headers_equivalent("gzip, br", "gzip"));
+        assert!(!headers_equivalent("gzip", "gzip, gzip"));
+        assert!(!headers_equivalent("gzip", "deflate"));
+        assert!(!headers_equivalent("custom", "custom2"));
+        assert!(!headers_equivalent("", "gzip"));
+    }
+
+    #[test]
+    fn test_same_coding_set() {
+        assert!(same_coding_set("gzip, br", "br, gzip"));
+        assert!(same_coding_set("gzip, Custom, br", "br, x-gzip, cUSTOM"));
+        assert!(same_coding_set("gzip, identity, br", " br ,, GZIP"));
+        assert!(same_coding_set("gzip, br, gzip", "gzip, gzip, br"));
+        assert!(same_coding_set("", "identity"));
+        assert!(same_coding_set("identity, ,", ""));
+
+        assert!(!same_coding_set("gzip, br", "gzip, br, gzip"));
+        assert!(!same_coding_set("gzip, br, gzip", "gzip, br, br"));
+        assert!(!same_coding_set("gzip, br", "gzip, zstd"));
+        assert!(!same_coding_set("gzip", ""));
+        assert!(!same_coding_set("custom", "custom2"));
+        assert!(!same_coding_set("deflate", "gzip"));
+    }
+
+    #[test]
+    fn test_is_identity_only() {
+        assert!(is_identity_only(""));
+        assert!(is_identity_only(" \t "));
+        assert!(is_identity_only(",,"));
+        assert!(is_identity_only("identity, identity"));
+        assert!(is_identity_only(" IDENTITY ,"));
+
+        assert!(!is_identity_only("gzip"));
+        assert!(!is_identity_only("identity, custom"));
+        assert!(!is_identity_only(", br,"));
+    }
+
+    #[test]
+    fn test_has_redundant_compression() {
+        assert!(has_redundant_compression("gzip, br"));
+        assert!(has_redundant_compression("gzip, gzip"));
+        assert!(has_redundant_compression("deflate, identity, custom, ZSTD"));
+        assert!(has_redundant_compression("pack200-gzip, x-compress"));
+
+        assert!(!has_redundant_compression("gzip, identity"));
+        assert!(!has_redundant_compression("br"));
+        assert!(has_redundant_compression("br, dcb"));
+        assert!(has_redundant_compression("dcz, zstd"));
+        assert!(has_redundant_compression("br, exi"));
+        assert!(!has_redundant_compression("br, aes128gcm, identity, custom"));
+        assert!(!has_redundant_compression("dcb, aes128gcm"));
+        assert!(!has_redundant_compression(""));
+    }
+
+    #[test]
+    fn test_header_contains() {
+        use self::StdContentEncoding::*;
+
+        assert!(header_contains("gzip", Gzip));
+        assert!(header_contains("br, GZip , custom", Gzip));
+        assert!(header_contains("x-gzip", Gzip));
+        assert!(!header_contains("br, custom", Gzip));
+        assert!(!header_contains("", Gzip));
+        assert!(header_contains("", Identity));
+        assert!(header_contains("br,", Identity));
+
+        assert!(header_contains_other("gzip, x-Custom", "X-CUSTOM"));
+        assert!(header_contains_other(" custom ", "custom"));
+        assert!(!header_contains_other("gzip, custom", "custom2"));
+        assert!(!header_contains_other("gzip", "gzip"));
+        assert!(!header_contains_other("", ""));
+    }
+
+    #[test]
+    fn test_other_codings() {
+        assert_eq!(other_codings("gzip, x-Custom ,br,\tother, x-gzip, custom2")
+                       .collect::<Vec<_>>(),
+                   vec!["custom2", "other", "x-Custom"]);
+        assert_eq!(other_codings("a, b, A").collect::<Vec<_>>(), vec!["A", "b", "a"]);
+        assert_eq!(other_codings("gzip, identity, , br, zstd").next(), None);
+        assert_eq!(other_codings("").next(), None);
+    }
+
+    #[test]
+    fn test_token() {
+        use self::StdContentEncoding::*;
+
+        const GZIP: &str = StdContentEncoding::Gzip.token();
+        assert_eq!(GZIP, "gzip");
+
+        for &enc in &[Aes128Gcm, Brotli, Compress, Dcb, Dcz, Deflate, EfficientXML, Gzip,
+                      Identity, Pack200Gzip, Zstd]
+        {
+            assert_eq!(enc.token(), enc.to_string());
+            assert_eq!(enc.token().parse::<StdContentEncoding>(), Ok(enc));
+        }
+    }
+
+    #[test]
+    fn test_all() {
+        use self::StdContentEncoding::*;
+
+        // This fails to compile when a variant is added, as a reminder to update `ALL`.
+        fn index(enc: StdContentEncoding) -> usize {
+            match enc {
+                Aes128Gcm => 0,
+                Brotli => 1,
+                Compress => 2,
+                Dcb => 3,
+                Dcz => 4,
+                Deflate => 5,
+                EfficientXML => 6,
+                Gzip => 7,
+                Identity => 8,
+                Pack200Gzip => 9,
+                Zstd => 10,
+            }
+        }
+
+        assert_eq!(StdContentEncoding::ALL.len(), 11);
+
+        for (i, &enc) in StdContentEncoding::ALL.iter().enumerate() {
+            assert_eq!(index(enc), i);
+            assert_eq!(enc.to_string().parse::<StdContentEncoding>(), Ok(enc));
+        }
+
+        assert!(StdContentEncoding::ALL.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_encoding_tokens() {
+        assert_eq!(ENCODING_TOKENS.len(), StdContentEncoding::ALL.len());
+
+        for (&(token, enc), &all) in ENCODING_TOKENS.iter().zip(StdContentEncoding::ALL) {
+            assert_eq!(enc, all);
+            assert_eq!(enc.token(), token);
+            assert_eq!(enc.to_string(), token);
+            assert_eq!(token.parse::<StdContentEncoding>(), Ok(enc));
+            assert_eq!(StdContentEncoding::from_canonical(token), Some(enc));
+            assert_eq!(token, token.to_ascii_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_as_ref() {
+        use self::StdContentEncoding::*;
+
+        fn name<S: AsRef<str>>(s: S) -> String {
+            s.as_ref().to_uppercase()
+        }
+
+        assert_eq!(name(Brotli), "BR");
+        assert_eq!(name(Pack200Gzip), "PACK200-GZIP");
+        assert_eq!(Gzip.as_ref(), "gzip");
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        use self::StdContentEncoding::*;
+
+        for &enc in StdContentEncoding::ALL {
+            let upper = enc.token().to_uppercase();
+            assert_eq!(StdContentEncoding::from_bytes(enc.token().as_bytes()), Some(enc));
+            assert_eq!(StdContentEncoding::from_bytes(upper.as_bytes()), Some(enc));
+        }
+
+        assert_eq!(StdContentEncoding::from_bytes(b"X-GZip"), Some(Gzip));
+        assert_eq!(StdContentEncoding::from_bytes(b"x-Compress"), Some(Compress));
+        assert_eq!(StdContentEncoding::from_bytes(b""), Some(Identity));
+
+        for &s in &[&b"gzi"[..], b"gzipp", b"gzi\xd0", b"b", b"brr", b"dcx", b"pack200-gzi",
+                    b"pack200-gzipx", b"x-compres", b"x_gzip", b"identit\x00", b" gzip",
+                    b"\xff\xff\xff\xff", b"aes128gcm-aes128gcm"]
+        {
+            assert_eq!(StdContentEncoding::from_bytes(s), None);
+        }
+    }
+
+    #[test]
+    fn test_compression_class() {
+        use super::StdContentEncoding::*;
+
+        assert_eq!(Aes128Gcm.compression_class(), CompressionClass::None);
+        assert_eq!(Brotli.compression_class(), CompressionClass::Strong);
+        assert_eq!(Compress.compression_class(), CompressionClass::Light);
+        assert_eq!(Dcb.compression_class(), CompressionClass::Strong);
+        assert_eq!(Dcz.compression_class(), CompressionClass::Strong);
+        assert_eq!(Deflate.compression_class(), CompressionClass::Light);
+        assert_eq!(EfficientXML.compression_class(), CompressionClass::Strong);
+        assert_eq!(Gzip.compression_class(), CompressionClass::Light);
+        assert_eq!(Identity.compression_class(), CompressionClass::None);
+        assert_eq!(Pack200Gzip.compression_class(), CompressionClass::Strong);
+        assert_eq!(Zstd.compression_class(), CompressionClass::Strong);
+
+        assert!(CompressionClass::None < CompressionClass::Light);
+        assert!(CompressionClass::Light < CompressionClass::Strong);
+    }
+
+    #[test]
+    fn test_is_deflate() {
+        use super::StdContentEncoding::*;
+
+        assert!(Deflate.is_deflate());
+
+        for &enc in StdContentEncoding::ALL {
+            assert_eq!(enc.is_deflate(), enc == Deflate);
+            assert_eq!(enc.deflate_ambiguity_note().is_some(), enc == Deflate);
+        }
+
+        let note = Deflate.deflate_ambiguity_note().unwrap();
+        assert!(note.contains("zlib"));
+        assert!(note.contains("raw"));
+    }
+
+    #[test]
+    fn test_magic_bytes() {
+        use super::StdContentEncoding::*;
+
+        assert_eq!(Gzip.magic_bytes(), Some(&[0x1f, 0x8b][..]));
+        assert_eq!(Pack200Gzip.magic_bytes(), Some(&[0x1f, 0x8b][..]));
+        assert_eq!(Compress.magic_bytes(), Some(&[0x1f, 0x9d][..]));
+        assert_eq!(Zstd.magic_bytes(), Some(&[0x28, 0xb5, 0x2f, 0xfd][..]));
+        assert_eq!(Dcb.magic_bytes(), Some(&b"\xffDCB"[..]));
+        assert_eq!(Dcz.magic_bytes(), Some(&b"\x5e\x2a\x4d\x18\x20\0\0\0"[..]));
+
+        assert_eq!(Identity.magic_bytes(), None);
+        assert_eq!(EfficientXML.magic_bytes(), None);
+        assert_eq!(Brotli.magic_bytes(), None);
+        assert_eq!(Deflate.magic_bytes(), None);
+        assert_eq!(Aes128Gcm.magic_bytes(), None);
+    }
+
+    #[test]
+    fn test_from_canonical() {
+        use self::StdContentEncoding::*;
+
+        assert_eq!(StdContentEncoding::from_canonical("gzip"), Some(Gzip));
+        assert_eq!(StdContentEncoding::from_canonical("GZIP"), None);
+        assert_eq!(StdContentEncoding::from_canonical("Gzip"), None);
+        assert_eq!(StdContentEncoding::from_canonical(" gzip"), None);
+        assert_eq!(StdContentEncoding::from_canonical("x-gzip"), None);
+        assert_eq!(StdContentEncoding::from_canonical(""), None);
+        assert_eq!(StdContentEncoding::from_canonical("custom"), None);
+
+        for &enc in &[Aes128Gcm, Brotli, Compress, Dcb, Dcz, Deflate, EfficientXML, Gzip,
+                      Identity, Pack200Gzip, Zstd]
+        {
+            assert_eq!(StdContentEncoding::from_canonical(enc.token()), Some(enc));
+        }
+    }
+
+    #[test]
+    fn test_commonly_supported() {
+        use self::StdContentEncoding::*;
+
+        assert!(Brotli.is_commonly_supported());
+        assert!(Deflate.is_commonly_supported());
+        assert!(Gzip.is_commonly_supported());
+        assert!(Identity.is_commonly_supported());
+        assert!(Zstd.is_commonly_supported());
+
+        assert!(!Aes128Gcm.is_commonly_supported());
+        assert!(!Compress.is_commonly_supported());
+        assert!(!Dcb.is_commonly_supported());
+        assert!(!Dcz.is_commonly_supported());
+        assert!(!EfficientXML.is_commonly_supported());
+        assert!(!Pack200Gzip.is_commonly_supported());
+    }
+
+    #[test]
+    fn test_display() {
+        use self::StdContentEncoding::*;
+
+        assert_eq!(format!("{}", Aes128Gcm), "aes128gcm");
+        assert_eq!(format!("{}", Brotli), "br");
+        assert_eq!(format!("{}", Compress), "compress");
+        assert_eq!(format!("{}", Dcb), "dcb");
+        assert_eq!(format!("{}", Dcz), "dcz");
+        assert_eq!(format!("{}", Deflate), "deflate");
+        assert_eq!(format!("{}", EfficientXML), "exi");
+        assert_eq!(format!("{}", Gzip), "gzip");
+        assert_eq!(format!("{}", Identity), "identity");
+        assert_eq!(format!("{}", Pack200Gzip), "pack200-gzip");
+        assert_eq!(format!("{}", Zstd), "zstd");
+        assert_eq!(format!("{}", ContentEncoding::new("x-gzip")), "gzip");
+        assert_eq!(format!("{}", ContentEncoding::new("X-Compress")), "compress");
+
+        for &enc in &[Aes128Gcm, Brotli, Compress, Dcb, Dcz, Deflate, EfficientXML, Gzip,
+                      Identity, Pack200Gzip, Zstd]
+        {
+            assert_eq!(enc.to_string().parse::<StdContentEncoding>(), Ok(enc));
+            assert_eq!(enc.to_string().to_uppercase().parse::<StdContentEncoding>(), Ok(enc));
+        }
+    }
+
+    #[test]
+    fn test_display_ce() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(format!("{}", Std(Gzip)), "gzip");
+        assert_eq!(format!("{}", ContentEncoding::new("  PaCK200-GZip ")), "pack200-gzip");
+        assert_eq!(format!("{}", Other("custom-enc")), "custom-enc");
+        assert_eq!(format!("{}", Other("X-Custom-Enc")), "X-Custom-Enc");
+        assert_eq!(format!("{}", ContentEncoding::new("\tMiXeD\t")), "MiXeD");
+
+        let header = "gzip, X-Custom, br";
+        let mut layers = content_encodings(header).map(|e| e.to_string()).collect::<Vec<_>>();
+        layers.reverse();
+        assert_eq!(layers.join(", "), header);
+    }
+
+    #[test]
+    fn test_new_ascii_lower() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        const TABLE: [ContentEncoding<'static>; 5] = [
+            ContentEncoding::new_ascii_lower("gzip"),
+            ContentEncoding::new_ascii_lower("x-gzip"),
+            ContentEncoding::new_ascii_lower(""),
+            ContentEncoding::new_ascii_lower("custom"),
+            ContentEncoding::new_ascii_lower("GZIP"),
+        ];
+
+        assert_eq!(TABLE, [Std(Gzip), Std(Gzip), Std(Identity), Other("custom"), Other("GZIP")]);
+
+        for &enc in &[Aes128Gcm, Brotli, Compress, Dcb, Dcz, Deflate, EfficientXML, Gzip,
+                      Identity, Pack200Gzip, Zstd]
+        {
+            assert_eq!(ContentEncoding::new_ascii_lower(enc.token()), Std(enc));
+        }
+
+        assert_eq!(ContentEncoding::new_ascii_lower("x-compress"), Std(Compress));
+        assert_eq!(ContentEncoding::new_ascii_lower(" br"), Other(" br"));
+
+        const BR: Option<StdContentEncoding> = StdContentEncoding::from_canonical("br");
+        assert_eq!(BR, Some(Brotli));
+    }
+
+    #[test]
+    fn test_new_strict() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(ContentEncoding::new_strict("gzip"), Ok(Std(Gzip)));
+        assert_eq!(ContentEncoding::new_strict(" \tBR\t "), Ok(Std(Brotli)));
+        assert_eq!(ContentEncoding::new_strict(""), Ok(Std(Identity)));
+        assert_eq!(ContentEncoding::new_strict("   "), Ok(Std(Identity)));
+        assert_eq!(ContentEncoding::new_strict("x-custom"), Ok(Other("x-custom")));
+        assert_eq!(ContentEncoding::new_strict("!#$%&'*+-.^_`|~09azAZ"),
+                   Ok(Other("!#$%&'*+-.^_`|~09azAZ")));
+
+        for &s in &["gz ip", "gz\tip", "\"gzip\"", "gz'\"ip", "gzip;q=1", "a/b", "(x)",
+                    "a\x00b", "a\x7fb", "ÆØБД❤", "gzïp"]
+        {
+            let err = ContentEncoding::new_strict(s).unwrap_err();
+            assert_eq!(err.token(), s);
+            assert!(err.to_string().starts_with("invalid content encoding token"));
+
+            let err = ParseEncodingError::from(err);
+            assert_eq!(err.kind(), ParseEncodingErrorKind::InvalidToken);
+            assert_eq!(err.token(), s);
+        }
+
+        assert_eq!(ContentEncoding::new_strict(" gz ip ").unwrap_err().token(), "gz ip");
+        assert_eq!(ContentEncoding::new_strict("gz ip").unwrap_err().to_string(),
+                   "invalid content encoding token \"gz ip\"");
+    }
+
+    #[test]
+    fn test_max_token_len() {
+        use self::ContentEncoding::*;
+
+        let max = "x".repeat(DEFAULT_MAX_TOKEN_LEN);
+        let over = "x".repeat(DEFAULT_MAX_TOKEN_LEN + 1);
+
+        assert_eq!(ContentEncoding::new_strict(&max), Ok(Other(&max[..])));
+        assert_eq!(ContentEncoding::new_strict(&format!(" {}\t", max)), Ok(Other(&max[..])));
+
+        let err = ContentEncoding::new_strict(&over).unwrap_err();
+        assert_eq!(err.kind(), ParseEncodingErrorKind::TooLong);
+        assert_eq!(err.token(), over);
+        assert_eq!(err.to_string(), "content encoding token too long (257 bytes)");
+        assert_eq!(ParseEncodingError::from(err).kind(), ParseEncodingErrorKind::TooLong);
+
+        // Overlong names are rejected even if they aren't tokens.
+        let spaced = "a ".repeat(150);
+        let err = ContentEncoding::new_strict(&spaced).unwrap_err();
+        assert_eq!(err.kind(), ParseEncodingErrorKind::TooLong);
+
+        assert_eq!(ContentEncoding::new_strict_max("abcd", 4), Ok(Other("abcd")));
+        assert_eq!(ContentEncoding::new_strict_max("abcde", 4).unwrap_err().kind(),
+                   ParseEncodingErrorKind::TooLong);
+        assert_eq!(ContentEncoding::new_strict_max("a c", 4).unwrap_err().kind(),
+                   ParseEncodingErrorKind::InvalidToken);
+        assert_eq!(ContentEncoding::new_strict_max("", 0), Ok(Std(StdContentEncoding::Identity)));
+
+        let header = format!("gzip, {}, br, {}", max, over);
+        let mut ce = content_encodings_checked(&header);
+        assert_eq!(ce.next().unwrap().unwrap_err().kind(), ParseEncodingErrorKind::TooLong);
+        assert_eq!(ce.next().unwrap(), Ok(Std(StdContentEncoding::Brotli)));
+        assert_eq!(ce.next().unwrap(), Ok(Other(&max[..])));
+        assert_eq!(ce.next().unwrap(), Ok(Std(StdContentEncoding::Gzip)));
+
+        let results = content_encodings_checked_max("gzip, abcdefgh, br", 7).collect::<Vec<_>>();
+        assert!(results[0].is_ok());
+        assert_eq!(results[1].unwrap_err().token(), "abcdefgh");
+        assert!(results[2].is_ok());
+    }
+
+    #[test]
+    fn test_from_std() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(ContentEncoding::from(Gzip), Std(Gzip));
+        let enc: ContentEncoding = Zstd.into();
+        assert_eq!(enc, Std(Zstd));
+
+        let layers: Vec<ContentEncoding> = vec![Brotli.into(), Identity.into()];
+        assert_eq!(layers, vec![Std(Brotli), Std(Identity)]);
+    }
+
+    #[test]
+    fn test_try_from() {
+        use std::convert::{TryFrom, TryInto};
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(ContentEncoding::try_from(" Deflate "), Ok(Std(Deflate)));
+        assert_eq!(ContentEncoding::try_from("x-custom"), Ok(Other("x-custom")));
+
+        let err = ContentEncoding::try_from("x-cus\x01tom").unwrap_err();
+        assert_eq!(err.token(), "x-cus\x01tom");
+
+        let res: Result<ContentEncoding, _> = "gzip".try_into();
+        assert_eq!(res, Ok(Std(Gzip)));
+    }
+
+    #[test]
+    fn test_decoder_registry() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        struct IdentityDecoder;
+
+        impl Decoder for IdentityDecoder {
+            fn decode(&self, input: &[u8]) -> Result<Vec<u8>, String> {
+                Ok(input.to_vec())
+            }
+        }
+
+        fn reverse(input: &[u8]) -> Result<Vec<u8>, String> {
+            Ok(input.iter().rev().cloned().collect())
+        }
+
+        let mut reg = DecoderRegistry::new();
+        reg.register(Std(Identity), IdentityDecoder)
+            .register(Other("Rev"), reverse)
+            .register(Other("drop1"), |input: &[u8]| match input.split_first() {
+                Some((_, rest)) => Ok(rest.to_vec()),
+                None => Err(String::from("empty input")),
+            });
+
+        // Layers are decoded outermost first: reversed, then the first byte dropped.
+        assert_eq!(reg.decode_layers("drop1, rev", b"abc"), Ok(b"ba".to_vec()));
+        assert_eq!(reg.decode_layers("rev, drop1", b"abc"), Ok(b"cb".to_vec()));
+        assert_eq!(reg.decode_layers("REV, identity, rev", b"abc"), Ok(b"abc".to_vec()));
+        assert_eq!(reg.decode_layers("identity", b"abc"), Ok(b"abc".to_vec()));
+        assert_eq!(reg.decode_layers("", b"abc"), Ok(b"abc".to_vec()));
+
+        assert_eq!(reg.decode_layers("gzip, rev", b"abc"),
+                   Err(DecodeError::Unsupported(ContentEncodingOwned::Std(Gzip))));
+        assert_eq!(reg.decode_layers("drop1, drop1", b"a"),
+                   Err(DecodeError::Failed(ContentEncodingOwned::Other("drop1".to_string()),
+                                           "empty input".to_string())));
+
+        assert!(reg.decoder(Other("REV")).is_some());
+        assert!(reg.decoder(Other("gzip")).is_none());
+
+        reg.register(Other("rev"), IdentityDecoder);
+        assert_eq!(reg.decode_layers("rev", b"abc"), Ok(b"abc".to_vec()));
+        assert_eq!(format!("{:?}", reg), "{Std(Identity), Other(\"Rev\"), Other(\"drop1\")}");
+
+        // Identity passes through without a decoder.
+        assert_eq!(DecoderRegistry::new().decode_layers("identity, ,", b"abc"),
+                   Ok(b"abc".to_vec()));
+
+        assert_eq!(DecodeError::Unsupported(ContentEncodingOwned::Std(Gzip)).to_string(),
+                   "no decoder for content encoding \"gzip\"");
+        assert_eq!(DecodeError::Failed(ContentEncodingOwned::Std(Brotli), "bad".to_string())
+                       .to_string(),
+                   "failed to decode content encoding \"br\": bad");
+    }
+
+    #[test]
+    fn test_builder() {
+        use self::StdContentEncoding::*;
+
+        let mut b = ContentEncodingBuilder::new();
+        b.push(Gzip).push_other("X-Custom").unwrap().push(Brotli);
+        let header = b.build();
+        assert_eq!(header, "gzip, X-Custom, br");
+
+        let mut ce = content_encodings(&header);
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Brotli));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Other("X-Custom"));
+        assert_eq!(ce.next().unwrap(), ContentEncoding::Std(Gzip));
+        assert!(ce.next().is_none());
+
+        let mut b = ContentEncodingBuilder::new();
+        b.push(Zstd);
+        for &name in &["", "bad token", "a,b", " pad", "\"q\"", "caf\u{e9}"] {
+            assert_eq!(b.push_other(name).unwrap_err().token(), name);
+        }
+        assert_eq!(b.build(), "zstd");
+
+        assert_eq!(ContentEncodingBuilder::new().build(), "");
+        assert_eq!(ContentEncodingBuilder::new().push(Gzip).build(), "gzip");
+        assert_eq!(ContentEncodingBuilder::new().push_other("a").unwrap().push(Dcz).build(),
+                   "a, dcz");
+
+        let mut b = ContentEncodingBuilder::new();
+        assert_eq!(b.push(Deflate).build(), "deflate");
+        assert_eq!(b.push(Brotli).build(), "deflate, br");
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(Std(Aes128Gcm).canonical_name(), "aes128gcm");
+        assert_eq!(Std(Brotli).canonical_name(), "br");
+        assert_eq!(Std(Compress).canonical_name(), "compress");
+        assert_eq!(Std(Dcb).canonical_name(), "dcb");
+        assert_eq!(Std(Dcz).canonical_name(), "dcz");
+        assert_eq!(Std(Deflate).canonical_name(), "deflate");
+        assert_eq!(Std(EfficientXML).canonical_name(), "exi");
+        assert_eq!(Std(Gzip).canonical_name(), "gzip");
+        assert_eq!(Std(Identity).canonical_name(), "identity");
+        assert_eq!(Std(Pack200Gzip).canonical_name(), "pack200-gzip");
+        assert_eq!(Std(Zstd).canonical_name(), "zstd");
+        assert_eq!(ContentEncoding::new(" X-GZIP ").canonical_name(), "gzip");
+        assert_eq!(ContentEncoding::new(" Custom-Enc ").canonical_name(), "Custom-Enc");
+
+        let name: &'static str = Std(Gzip).canonical_name();
+        assert_eq!(name, "gzip");
+    }
+
+    #[test]
+    fn test_token_len() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(Std(Gzip).token_len(), 4);
+        assert_eq!(Std(Pack200Gzip).token_len(), 12);
+        assert_eq!(ContentEncoding::new(" X-GZIP ").token_len(), 4);
+        assert_eq!(ContentEncoding::new("").token_len(), "identity".len());
+        assert_eq!(Other("custom").token_len(), 6);
+        assert_eq!(Other("ÆØ").token_len(), 4);
+        assert_eq!(Other("enc-😀").token_len(), 8);
+
+        for (enc, range) in content_encodings_spanned(" café ,\tx-ÆØ , br") {
+            if let Other(_) = enc {
+                assert_eq!(enc.token_len(), range.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_helpers() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert!(Std(Identity).is_identity());
+        assert!(ContentEncoding::new("").is_identity());
+        assert!(!Std(Gzip).is_identity());
+        assert!(!Other("identity-ish").is_identity());
+
+        assert!(Std(Identity).is_std());
+        assert!(Std(Zstd).is_std());
+        assert!(!Other("abc").is_std());
+
+        assert_eq!(Std(Deflate).as_std(), Some(Deflate));
+        assert_eq!(Std(Identity).as_std(), Some(Identity));
+        assert_eq!(Other("abc").as_std(), None);
+
+        assert_eq!(Std(Zstd).into_std(), Some(Zstd));
+        assert_eq!(Other("abc").into_std(), None);
+        assert_eq!(core::mem::size_of::<Option<StdContentEncoding>>(), 1);
+
+        let header = "gzip, custom, identity, x-other, br";
+        assert_eq!(content_encodings(header).filter_map(ContentEncoding::into_std)
+                       .collect::<Vec<_>>(),
+                   vec![Brotli, Identity, Gzip]);
+
+        let encs = content_encodings(header).collect::<Vec<_>>();
+        assert_eq!(encs.iter().filter_map(ContentEncoding::as_std).count(), 3);
+    }
+
+    #[test]
+    fn test_to_owned() {
+        use self::StdContentEncoding::*;
+
+        let owned = {
+            let header = String::from("gzip, X-Custom");
+            content_encodings(&header).map(|e| e.to_owned()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(owned, vec![
+            ContentEncodingOwned::Other(String::from("X-Custom")),
+            ContentEncodingOwned::Std(Gzip),
+        ]);
+        assert_eq!(owned[0].as_borrowed(), ContentEncoding::Other("X-Custom"));
+        assert_eq!(owned[0].as_borrowed(), "x-custom");
+        assert_eq!(owned[1].as_borrowed(), ContentEncoding::Std(Gzip));
+        assert_eq!(owned[0].to_string(), "X-Custom");
+        assert_eq!(owned[1].to_string(), "gzip");
+
+        assert_eq!(ContentEncodingOwned::from(ContentEncoding::new("br")),
+                   ContentEncodingOwned::Std(Brotli));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        fn hash(enc: ContentEncoding) -> u64 {
+            let mut h = DefaultHasher::new();
+            enc.hash(&mut h);
+            h.finish()
+        }
+
+        assert_eq!(Other("GZIP").canonicalize(), Std(Gzip));
+        assert_eq!(Other(" x-gzip\t").canonicalize(), Std(Gzip));
+        assert_eq!(Other("br").canonicalize(), Std(Brotli));
+        assert_eq!(Other("").canonicalize(), Std(Identity));
+        assert_eq!(Other("Custom").canonicalize(), Other("Custom"));
+        assert_eq!(Other(" custom ").canonicalize(), Other("custom"));
+        assert_eq!(Std(Zstd).canonicalize(), Std(Zstd));
+
+        assert_eq!(hash(Other("GZIP").canonicalize()), hash(Std(Gzip)));
+        assert_eq!(hash(Other("Zstd").canonicalize()), hash(ContentEncoding::new("zstd")));
+    }
+
+    #[test]
+    fn test_eq_std() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert!(Std(Gzip) == Gzip);
+        assert!(Gzip == Std(Gzip));
+        assert!(ContentEncoding::new("x-gzip") == Gzip);
+        assert!(ContentEncoding::new("") == Identity);
+        assert!(Std(Gzip) != Brotli);
+        assert!(Brotli != Std(Gzip));
+        assert!(Other("gzip") != Gzip);
+        assert!(Gzip != Other("gzip"));
+
+        for &enc in StdContentEncoding::ALL {
+            assert!(Std(enc) == enc);
+            assert!(Other(enc.token()) != enc);
+        }
+    }
+
+    #[test]
+    fn test_eq_ignoring_x_prefix() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert!(Other("x-foo").eq_ignoring_x_prefix("foo"));
+        assert!(Other("foo").eq_ignoring_x_prefix("X-Foo"));
+        assert!(Other("X-FOO").eq_ignoring_x_prefix(" x-foo "));
+        assert!(Std(Gzip).eq_ignoring_x_prefix("x-gzip"));
+        assert!(Std(Gzip).eq_ignoring_x_prefix("gzip"));
+        assert!(Std(Compress).eq_ignoring_x_prefix("X-Compress"));
+        assert!(Other("x-br").eq_ignoring_x_prefix("br"));
+
+        assert!(!Other("x-foo").eq_ignoring_x_prefix("bar"));
+        assert!(!Other("xfoo").eq_ignoring_x_prefix("foo"));
+        assert!(!Other("x-x-foo").eq_ignoring_x_prefix("foo"));
+        assert!(!Std(Gzip).eq_ignoring_x_prefix("x-br"));
+        assert!(!Other("Æx-foo").eq_ignoring_x_prefix("foo"));
+    }
+
+    #[test]
+    fn test_eq_str() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert!(Std(Gzip) == "gzip");
+        assert!(Std(Gzip) == "GZip");
+        assert!(Std(Gzip) != "x-gzip");
+        assert!(Std(Gzip) != "br");
+        assert!(Std(Brotli) == "br");
+        assert!(Std(Pack200Gzip) == *"PACK200-gzip");
+        assert!(ContentEncoding::new("") == "identity");
+
+        assert!(Other("Custom") == "custom");
+        assert!(Other("Custom") == "CUSTOM");
+        assert!(Other("Custom") == *"Custom");
+        assert!(Other("Custom") != "custom2");
+        assert!(Other("ÆØ") == "ÆØ");
+        assert!(Other("ÆØ") != "æø");
+
+        assert!(content_encodings("gzip, Custom").any(|e| e == "custom"));
+    }
+
+    #[test]
+    fn test_other_original() {
+        use self::StdContentEncoding::*;
+
+        let enc = ContentEncoding::new("  X-Custom-Enc\t");
+        assert_eq!(enc.other_original(), Some("X-Custom-Enc"));
+        assert!(enc == "x-custom-enc");
+        assert!(enc == "X-CUSTOM-ENC");
+        assert_eq!(enc.to_string(), "X-Custom-Enc");
+
+        assert_eq!(ContentEncoding::new("GZIP").other_original(), None);
+        assert_eq!(ContentEncoding::Std(Identity).other_original(), None);
+    }
+
+    #[test]
+    fn test_default() {
+        assert_eq!(StdContentEncoding::default(), StdContentEncoding::Identity);
+        assert_eq!(ContentEncoding::default(), ContentEncoding::Std(StdContentEncoding::Identity));
+        assert!(ContentEncoding::default().is_identity());
+
+        let header: Option<&str> = None;
+        let enc = header.map(ContentEncoding::new).unwrap_or_default();
+        assert_eq!(enc, ContentEncoding::new(""));
+    }
+
+    #[test]
+    fn test_encode() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(encode_content_encodings(content_encodings("deflate, br, identity")),
+                   "deflate, br, identity");
+        assert_eq!(encode_content_encodings(content_encodings("  GZIP,x-Custom ")),
+                   "gzip, x-Custom");
+        assert_eq!(encode_content_encodings(vec![Std(Brotli), Std(Gzip)]), "gzip, br");
+        assert_eq!(encode_content_encodings(vec![Other("abc")]), "abc");
+        assert_eq!(encode_content_encodings(None), "");
+
+        let filtered = content_encodings("gzip, identity, br, identity")
+            .filter(|e| *e != Std(Identity));
+        assert_eq!(encode_content_encodings(filtered), "gzip, br");
+
+        let header = encode_content_encodings(content_encodings("abc, exi"));
+        let mut ce = content_encodings(&header);
+        assert_eq!(ce.next().unwrap(), Std(EfficientXML));
+        assert_eq!(ce.next().unwrap(), Other("abc"));
+        assert!(ce.next().is_none());
+    }
+
+    #[test]
+    fn test_canonical_header() {
+        assert_eq!(canonical_header("GZIP , identity, Br"), "gzip, br");
+        assert_eq!(canonical_header("gzip, br"), "gzip, br");
+        assert_eq!(canonical_header("x-gzip,br"), "gzip, br");
+        assert_eq!(canonical_header(",\tX-Custom ,, zstd,"), "x-custom, zstd");
+        assert_eq!(canonical_header("br, gzip"), "br, gzip");
+        assert_eq!(canonical_header("ÆØ"), "ÆØ");
+        assert_eq!(canonical_header("identity"), "");
+        assert_eq!(canonical_header(""), "");
+    }
+
+    #[test]
+    fn test_cache_key_component() {
+        for s in &["gzip, br", "GZIP,BR", " x-gzip , identity, Br ", "gzip,,br,"] {
+            assert_eq!(cache_key_component(s), "gzip, br");
+        }
+
+        for s in &["", "identity", " IDENTITY ,", ",,"] {
+            assert_eq!(cache_key_component(s), "identity");
+        }
+
+        assert_eq!(cache_key_component("X-Custom"), "x-custom");
+        assert_ne!(cache_key_component("br, gzip"), cache_key_component("gzip, br"));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = "custom-enc".parse::<StdContentEncoding>().unwrap_err();
+        assert_eq!(err.kind(), ParseEncodingErrorKind::Unknown);
+        assert_eq!(err.token(), "custom-enc");
+        assert!(err.to_string().contains("custom-enc"));
+
+        let err = "ÆØБД❤".parse::<StdContentEncoding>().unwrap_err();
+        assert_eq!(err.token(), "ÆØБД❤");
+        assert!(err.to_string().contains("ÆØБД❤"));
+
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        assert!(err.to_string().contains("ÆØБД❤"));
+
+        assert_eq!(ContentEncoding::new("custom-enc"), ContentEncoding::Other("custom-enc"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        extern crate serde_json;
+
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        assert_eq!(serde_json::to_string(&Gzip).unwrap(), "\"gzip\"");
+        assert_eq!(serde_json::to_string(&Pack200Gzip).unwrap(), "\"pack200-gzip\"");
+        assert_eq!(serde_json::from_str::<StdContentEncoding>("\"BR\"").unwrap(), Brotli);
+        assert!(serde_json::from_str::<StdContentEncoding>("\"custom\"").is_err());
+
+        for &enc in &[Aes128Gcm, Brotli, Compress, Dcb, Dcz, Deflate, EfficientXML, Gzip,
+                      Identity, Pack200Gzip, Zstd]
+        {
+            let json = serde_json::to_string(&enc).unwrap();
+            assert_eq!(serde_json::from_str::<StdContentEncoding>(&json).unwrap(), enc);
+        }
+
+        assert_eq!(serde_json::to_string(&Std(Zstd)).unwrap(), "\"zstd\"");
+        assert_eq!(serde_json::to_string(&Other("X-Custom")).unwrap(), "\"X-Custom\"");
+        assert_eq!(serde_json::from_str::<ContentEncoding>("\"x-gzip\"").unwrap(), Std(Gzip));
+        assert_eq!(serde_json::from_str::<ContentEncoding>("\"X-Custom\"").unwrap(),
+                   Other("X-Custom"));
+        assert!(serde_json::from_str::<ContentEncoding>("\"esc\\u0061ped\"").is_err());
+
+        let layers = content_encodings("gzip, custom, br").collect::<Vec<_>>();
+        let json = serde_json::to_string(&layers).unwrap();
+        assert_eq!(json, r#"["br","custom","gzip"]"#);
+        assert_eq!(serde_json::from_str::<Vec<ContentEncoding>>(&json).unwrap(), layers);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_brottli_alias() {
+        assert_eq!(StdContentEncoding::Brottli, StdContentEncoding::Brotli);
+        assert_eq!(ContentEncoding::new("br"), ContentEncoding::Std(StdContentEncoding::Brottli));
+        assert_eq!(format!("{:?}", StdContentEncoding::Brottli), "Brotli");
+
+        match StdContentEncoding::Brotli {
+            StdContentEncoding::Brottli => {}
+            _ => panic!(),
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod test_no_std {
+    use super::*;
+
+    #[test]
+    fn test_no_std() {
+        use self::StdContentEncoding::*;
+        use self::ContentEncoding::*;
+
+        let mut ce = content_encodings("deflate, BR, custom");
+        assert_eq!(ce.next(), Some(Other("custom")));
+        assert_eq!(ce.next(), Some(Std(Brotli)));
+        assert_eq!(ce.next(), Some(Std(Deflate)));
+        assert_eq!(ce.next(), None);
+
+        assert_eq!("zstd".parse::<StdContentEncoding>(), Ok(Zstd));
+        assert!("custom".parse::<StdContentEncoding>().is_err());
+
+        let mut ce = content_encodings_checked("gzip, bad token");
+        assert_eq!(ce.next().unwrap().unwrap_err().token(), "bad token");
+        assert_eq!(ce.next().unwrap(), Ok(Std(Gzip)));
     }
 }